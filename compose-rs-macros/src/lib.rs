@@ -0,0 +1,272 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Expr, ExprArray, ItemFn, Lit, LitBool, LitStr};
+
+#[derive(Default)]
+struct ComposeTestArgs {
+    file: Option<LitStr>,
+    services: Vec<LitStr>,
+    keep_on_failure: Option<LitBool>,
+}
+
+impl ComposeTestArgs {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("file") {
+            self.file = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("services") {
+            let array: ExprArray = meta.value()?.parse()?;
+
+            for elem in array.elems {
+                match elem {
+                    Expr::Lit(syn::ExprLit {
+                        lit: Lit::Str(service),
+                        ..
+                    }) => self.services.push(service),
+                    elem => {
+                        return Err(syn::Error::new_spanned(
+                            elem,
+                            "expected a service name string",
+                        ))
+                    }
+                }
+            }
+        } else if meta.path.is_ident("keep_on_failure") {
+            self.keep_on_failure = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error("unsupported compose_test argument"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Run a test against a compose stack that is started before and torn down after it.
+///
+/// The test function may take up to two arguments, which receive a `&Compose` and the
+/// resolved `&Endpoints` of the stack. Async functions are run with `#[tokio::test]`.
+///
+/// ```ignore
+/// #[compose_test(file = "tests/stack.yml", services = ["db"])]
+/// fn connects(compose: &Compose, endpoints: &Endpoints) {
+///     let addr = endpoints.first("db", 5432).unwrap();
+///     // ...
+/// }
+/// ```
+///
+/// # Arguments
+///
+/// - `file`: path to the docker-compose file, absolute or relative to the crate root (required)
+/// - `services`: only start these services and their dependencies
+/// - `keep_on_failure`: keep the stack alive if the test fails
+#[proc_macro_attribute]
+pub fn compose_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut parsed = ComposeTestArgs::default();
+    let parser = syn::meta::parser(|meta| parsed.parse(meta));
+    parse_macro_input!(args with parser);
+
+    let input = parse_macro_input!(item as ItemFn);
+
+    match expand(parsed, input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(args: ComposeTestArgs, input: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let file = args.file.ok_or_else(|| {
+        syn::Error::new(Span::call_site(), "missing `file` argument of compose_test")
+    })?;
+
+    if input.sig.inputs.len() > 2 {
+        return Err(syn::Error::new_spanned(
+            &input.sig.inputs,
+            "compose_test functions take at most a `&Compose` and an `&Endpoints` argument",
+        ));
+    }
+
+    // Relative paths are resolved against the crate the test is defined in
+    let path = match std::path::Path::new(&file.value()).is_absolute() {
+        true => quote! { #file },
+        false => quote! { concat!(env!("CARGO_MANIFEST_DIR"), "/", #file) },
+    };
+
+    let services = args.services;
+    let keep_on_failure = args
+        .keep_on_failure
+        .unwrap_or_else(|| LitBool::new(false, Span::call_site()));
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = input;
+
+    let name = &sig.ident;
+    let output = &sig.output;
+    let is_async = sig.asyncness.is_some();
+
+    let mut inner_sig = sig.clone();
+    inner_sig.ident = syn::Ident::new("__compose_test_inner", Span::call_site());
+
+    let call_args = match sig.inputs.len() {
+        0 => quote! {},
+        1 => quote! { &__fixture },
+        _ => quote! { &__fixture, &__endpoints },
+    };
+
+    let call = match is_async {
+        true => quote! { __compose_test_inner(#call_args).await },
+        false => quote! { __compose_test_inner(#call_args) },
+    };
+
+    let test_attr = match is_async {
+        true => quote! { #[::tokio::test] },
+        false => quote! { #[test] },
+    };
+
+    let asyncness = &sig.asyncness;
+
+    Ok(quote! {
+        #test_attr
+        #(#attrs)*
+        #vis #asyncness fn #name() #output {
+            #inner_sig #block
+
+            let __fixture = ::compose_rs::fixture::ComposeFixture::builder(#path)
+            #(.service(#services))*
+            .keep_on_failure(#keep_on_failure)
+            .start()
+            .expect("Failed to start compose stack");
+
+            #[allow(unused_variables)]
+            let __endpoints = __fixture
+                .endpoints()
+                .expect("Failed to resolve compose endpoints");
+
+            #call
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::TokenStream;
+    use syn::parse::Parser;
+
+    use super::*;
+
+    fn args(tokens: TokenStream) -> syn::Result<ComposeTestArgs> {
+        let mut parsed = ComposeTestArgs::default();
+        syn::meta::parser(|meta| parsed.parse(meta)).parse2(tokens)?;
+        Ok(parsed)
+    }
+
+    fn expand_test(args_tokens: TokenStream, item: TokenStream) -> syn::Result<String> {
+        let expanded = expand(args(args_tokens)?, syn::parse2(item)?)?;
+
+        // The expansion has to be a single function again
+        syn::parse2::<ItemFn>(expanded.clone()).unwrap();
+        Ok(expanded.to_string())
+    }
+
+    #[test]
+    fn test_sync() {
+        let expanded = expand_test(
+            quote! { file = "tests/stack.yml" },
+            quote! {
+                fn connects(compose: &Compose, endpoints: &Endpoints) {
+                    assert!(endpoints.first("db", 5432).is_some());
+                }
+            },
+        )
+        .unwrap();
+
+        assert!(expanded.starts_with(&quote! { #[test] }.to_string()));
+        assert!(expanded.contains(&quote! { fn connects() }.to_string()));
+        assert!(expanded.contains(
+            &quote! { concat!(env!("CARGO_MANIFEST_DIR"), "/", "tests/stack.yml") }.to_string()
+        ));
+        assert!(expanded.contains(&quote! { .keep_on_failure(false) }.to_string()));
+        assert!(expanded
+            .contains(&quote! { __compose_test_inner(&__fixture, &__endpoints) }.to_string()));
+        assert!(!expanded.contains(".await"));
+    }
+
+    #[test]
+    fn test_tokio() {
+        let expanded = expand_test(
+            quote! { file = "tests/stack.yml", keep_on_failure = true },
+            quote! {
+                async fn connects(compose: &Compose) {}
+            },
+        )
+        .unwrap();
+
+        assert!(expanded.starts_with(&quote! { #[::tokio::test] }.to_string()));
+        assert!(expanded.contains(&quote! { async fn connects() }.to_string()));
+        assert!(expanded.contains(&quote! { .keep_on_failure(true) }.to_string()));
+        assert!(expanded.contains(&quote! { __compose_test_inner(&__fixture).await }.to_string()));
+    }
+
+    #[test]
+    fn test_absolute_file() {
+        let expanded = expand_test(
+            quote! { file = "/srv/stack.yml" },
+            quote! { fn starts() {} },
+        )
+        .unwrap();
+
+        assert!(expanded.contains(&quote! { builder("/srv/stack.yml") }.to_string()));
+        assert!(!expanded.contains("CARGO_MANIFEST_DIR"));
+    }
+
+    #[test]
+    fn test_services() {
+        let parsed = args(quote! { file = "stack.yml", services = ["db", "cache"] }).unwrap();
+        let services = parsed
+            .services
+            .iter()
+            .map(LitStr::value)
+            .collect::<Vec<_>>();
+        assert_eq!(services, ["db", "cache"]);
+
+        let expanded = expand_test(
+            quote! { file = "stack.yml", services = ["db", "cache"] },
+            quote! { fn starts() {} },
+        )
+        .unwrap();
+        assert!(expanded.contains(&quote! { .service("db") .service("cache") }.to_string()));
+        assert!(expanded.contains(&quote! { __compose_test_inner() }.to_string()));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let error = |args_tokens: TokenStream, item: TokenStream| {
+            expand_test(args_tokens, item).unwrap_err().to_string()
+        };
+        let item = quote! { fn starts() {} };
+
+        assert_eq!(
+            error(quote! { path = "stack.yml" }, item.clone()),
+            "unsupported compose_test argument"
+        );
+        assert_eq!(
+            error(quote! { file = "stack.yml", services = [db] }, item.clone()),
+            "expected a service name string"
+        );
+        assert!(args(quote! { file = stack }).is_err());
+        assert_eq!(
+            error(quote! { services = ["db"] }, item),
+            "missing `file` argument of compose_test"
+        );
+        assert_eq!(
+            error(
+                quote! { file = "stack.yml" },
+                quote! { fn starts(a: &Compose, b: &Endpoints, c: u8) {} }
+            ),
+            "compose_test functions take at most a `&Compose` and an `&Endpoints` argument"
+        );
+    }
+}
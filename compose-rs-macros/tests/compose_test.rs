@@ -0,0 +1,22 @@
+use compose_rs::{Compose, Endpoints};
+use compose_rs_macros::compose_test;
+
+#[compose_test(file = "tests/stack.yml")]
+#[ignore = "requires a docker daemon"]
+fn starts_stack(compose: &Compose, endpoints: &Endpoints) {
+    assert!(compose.project_name().is_some());
+    assert!(endpoints.first("web", 80).is_some());
+}
+
+#[compose_test(file = "tests/stack.yml", services = ["web"], keep_on_failure = true)]
+#[ignore = "requires a docker daemon"]
+fn starts_services(compose: &Compose) {
+    assert!(compose.project_name().is_some());
+}
+
+// Runs without docker, the fixture fails before compose is invoked
+#[compose_test(file = "tests/missing.yml")]
+#[should_panic(expected = "Failed to start compose stack")]
+fn fails_without_file() {
+    unreachable!("the stack can't start without a compose file");
+}
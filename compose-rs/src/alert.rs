@@ -0,0 +1,546 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::Write,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::{
+    command::stats::Stats,
+    container::{Container, Status},
+    http, Compose, ComposeCommand, ComposeError,
+};
+
+/// The condition a [Rule] checks for every container.
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// CPU usage in percent is above the threshold
+    CpuAbove(f64),
+    /// Memory usage in bytes is above the threshold
+    MemUsageAbove(u64),
+    /// Memory usage is above the given percentage of the container's limit
+    MemLimitAbove(f64),
+    /// The container restarted at least `count` times within `window`
+    Restarts { count: u32, window: Duration },
+}
+
+/// An alerting rule, e.g. "cpu_perc > 90 for 30s on service api".
+#[derive(Debug, Clone)]
+pub struct Rule {
+    name: String,
+    condition: Condition,
+    service: Option<String>,
+    for_duration: Duration,
+}
+
+impl Rule {
+    pub fn new(name: impl ToString, condition: Condition) -> Self {
+        Self {
+            name: name.to_string(),
+            condition,
+            service: None,
+            for_duration: Duration::ZERO,
+        }
+    }
+
+    /// Only evaluate the rule for containers of the given service.
+    pub fn service(mut self, service: impl ToString) -> Self {
+        self.service = Some(service.to_string());
+        self
+    }
+
+    /// The condition has to hold for this long before the alert fires.
+    pub fn for_duration(mut self, duration: Duration) -> Self {
+        self.for_duration = duration;
+        self
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// A firing or resolved transition of a [Rule] for a single container.
+#[derive(Serialize, Debug, Clone)]
+pub struct Alert {
+    pub rule: String,
+    pub state: AlertState,
+    pub service: Option<String>,
+    pub container: String,
+    /// The observed value that triggered the transition
+    pub value: f64,
+    pub time: SystemTime,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            AlertState::Firing => "FIRING",
+            AlertState::Resolved => "RESOLVED",
+        };
+
+        write!(
+            f,
+            "[{state}] {} on {} (value: {:.2})",
+            self.rule, self.container, self.value
+        )
+    }
+}
+
+/// Receives alerts emitted by an [AlertEngine].
+pub trait AlertSink: Send {
+    fn send(&mut self, alert: &Alert) -> Result<(), ComposeError>;
+}
+
+impl<F> AlertSink for F
+where
+    F: FnMut(&Alert) + Send,
+{
+    fn send(&mut self, alert: &Alert) -> Result<(), ComposeError> {
+        self(alert);
+        Ok(())
+    }
+}
+
+/// Writes one line per alert to the given writer.
+pub struct LogSink<W: Write + Send> {
+    writer: W,
+}
+
+impl<W: Write + Send> LogSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write + Send> AlertSink for LogSink<W> {
+    fn send(&mut self, alert: &Alert) -> Result<(), ComposeError> {
+        writeln!(self.writer, "{alert}")?;
+        Ok(())
+    }
+}
+
+/// POSTs every alert as JSON to a plain HTTP endpoint, e.g. `http://127.0.0.1:9000/alerts`.
+pub struct WebhookSink {
+    host: String,
+    path: String,
+    timeout: Duration,
+}
+
+impl WebhookSink {
+    /// Create a webhook sink for the given `http://` URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ComposeError::InvalidArguments] if the URL is not a plain `http://` URL.
+    pub fn new(url: &str) -> Result<Self, ComposeError> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            ComposeError::InvalidArguments(format!("Unsupported webhook URL: {url}"))
+        })?;
+
+        let (host, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+
+        if host.is_empty() {
+            return Err(ComposeError::InvalidArguments(format!(
+                "Missing host in webhook URL: {url}"
+            )));
+        }
+
+        let host = match host.contains(':') && !host.ends_with(']') {
+            true => host.to_string(),
+            false => format!("{host}:80"),
+        };
+
+        Ok(Self {
+            host,
+            path: path.to_string(),
+            timeout: Duration::from_secs(5),
+        })
+    }
+
+    /// Timeout for connecting to and talking to the endpoint, defaults to 5 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl AlertSink for WebhookSink {
+    fn send(&mut self, alert: &Alert) -> Result<(), ComposeError> {
+        let body = serde_json::to_string(alert)?;
+
+        let status = http::request(
+            self.host.as_str(),
+            &self.host,
+            "POST",
+            &self.path,
+            Some(("application/json", &body)),
+            self.timeout,
+        )?;
+
+        if !(200..300).contains(&status) {
+            return Err(ComposeError::IoError(std::io::Error::other(format!(
+                "Webhook responded with status {status}"
+            ))));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct RuleState {
+    pending_since: Option<Instant>,
+    firing: bool,
+}
+
+#[derive(Default)]
+struct ContainerHistory {
+    status: Option<Status>,
+    restarts: VecDeque<Instant>,
+}
+
+/// Evaluates [Rule]s against the stats stream and container states and emits [Alert]s.
+#[derive(Default)]
+pub struct AlertEngine {
+    rules: Vec<Rule>,
+    sinks: Vec<Box<dyn AlertSink>>,
+    states: HashMap<(usize, String), RuleState>,
+    history: HashMap<String, ContainerHistory>,
+    services: HashMap<String, String>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn sink(mut self, sink: impl AlertSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Evaluate the resource rules against a frame of the stats stream.
+    pub fn observe_stats(&mut self, stats: &[Stats]) -> Result<(), ComposeError> {
+        self.observe_stats_at(stats, Instant::now())
+    }
+
+    /// Track container states from `ps` and evaluate the restart rules.
+    pub fn observe_containers(&mut self, containers: &[Container]) -> Result<(), ComposeError> {
+        self.observe_containers_at(containers, Instant::now())
+    }
+
+    /// Stream stats of the compose stack and evaluate all rules until the stream ends.
+    pub fn run(mut self, compose: &Compose) -> Result<(), ComposeError> {
+        for stats in compose.stats().stream()? {
+            let stats = stats?;
+            let containers = compose.ps().exec()?;
+
+            self.observe_containers(&containers)?;
+            self.observe_stats(&stats)?;
+        }
+
+        Ok(())
+    }
+
+    fn observe_stats_at(&mut self, stats: &[Stats], now: Instant) -> Result<(), ComposeError> {
+        for stat in stats {
+            for index in 0..self.rules.len() {
+                let value = match self.rules[index].condition {
+                    Condition::CpuAbove(threshold) => {
+                        let value = stat.cpu_perc.value();
+                        (value, value > threshold)
+                    }
+                    Condition::MemUsageAbove(threshold) => {
+                        let value = stat.mem_usage.usage();
+                        (value as f64, value > threshold)
+                    }
+                    Condition::MemLimitAbove(threshold) => {
+                        let limit = stat.mem_usage.limit();
+                        if limit == 0 {
+                            continue;
+                        }
+
+                        let value = stat.mem_usage.usage() as f64 / limit as f64 * 100.0;
+                        (value, value > threshold)
+                    }
+                    Condition::Restarts { .. } => continue,
+                };
+
+                self.evaluate(index, &stat.name, value, now)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn observe_containers_at(
+        &mut self,
+        containers: &[Container],
+        now: Instant,
+    ) -> Result<(), ComposeError> {
+        for container in containers {
+            self.services
+                .insert(container.name.clone(), container.service.clone());
+
+            let history = self.history.entry(container.name.clone()).or_default();
+            let status = container.status.status.clone();
+
+            // A restart is either the edge into the restarting state or, if that fell between
+            // two observations, the container coming back up after it stopped
+            let restarted = match (&history.status, &status) {
+                (Some(Status::Restarting), Status::Restarting) => false,
+                (_, Status::Restarting) => true,
+                (Some(Status::Exited | Status::Dead), Status::Up) => true,
+                _ => false,
+            };
+            if restarted {
+                history.restarts.push_back(now);
+            }
+            history.status = Some(status);
+        }
+
+        for index in 0..self.rules.len() {
+            let Condition::Restarts { count, window } = self.rules[index].condition else {
+                continue;
+            };
+
+            for container in containers {
+                let history = self.history.entry(container.name.clone()).or_default();
+                while history
+                    .restarts
+                    .front()
+                    .is_some_and(|restart| now.duration_since(*restart) > window)
+                {
+                    history.restarts.pop_front();
+                }
+
+                let value = history.restarts.len();
+                self.evaluate(
+                    index,
+                    &container.name,
+                    (value as f64, value >= count as usize),
+                    now,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn evaluate(
+        &mut self,
+        index: usize,
+        container: &str,
+        (value, matched): (f64, bool),
+        now: Instant,
+    ) -> Result<(), ComposeError> {
+        let rule = &self.rules[index];
+        let service = self.service_of(container, rule.service.as_deref());
+
+        if let Some(filter) = &rule.service {
+            if service.as_ref() != Some(filter) {
+                return Ok(());
+            }
+        }
+
+        let state = self
+            .states
+            .entry((index, container.to_string()))
+            .or_default();
+
+        let transition = if matched {
+            let since = *state.pending_since.get_or_insert(now);
+            if !state.firing && now.duration_since(since) >= rule.for_duration {
+                state.firing = true;
+                Some(AlertState::Firing)
+            } else {
+                None
+            }
+        } else {
+            state.pending_since = None;
+            if state.firing {
+                state.firing = false;
+                Some(AlertState::Resolved)
+            } else {
+                None
+            }
+        };
+
+        if let Some(state) = transition {
+            let alert = Alert {
+                rule: rule.name.clone(),
+                state,
+                service,
+                container: container.to_string(),
+                value,
+                time: SystemTime::now(),
+            };
+
+            for sink in &mut self.sinks {
+                sink.send(&alert)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The service of a container as reported by `ps`. Containers that were only seen in
+    /// stats, e.g. during a replay, are matched against `candidate` by the
+    /// `<project>-<service>-<number>` name compose gives them.
+    fn service_of(&self, container: &str, candidate: Option<&str>) -> Option<String> {
+        if let Some(service) = self.services.get(container) {
+            return Some(service.clone());
+        }
+
+        let candidate = candidate?;
+        let (rest, number) = container.rsplit_once(['-', '_'])?;
+        let named = number.parse::<u32>().is_ok()
+            && rest
+                .strip_suffix(candidate)
+                .is_some_and(|project| project.ends_with(['-', '_']));
+
+        named.then(|| candidate.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::ContainerStatus;
+
+    fn stats(cpu: &str) -> Stats {
+        serde_json::from_str(&format!(
+            r#"{{"BlockIO":"0B / 0B","CPUPerc":"{cpu}%","Container":"9ca40acb565a","ID":"9ca40acb565a","MemPerc":"0.13%","MemUsage":"10MiB / 100MiB","Name":"examples-api-1","NetIO":"0B / 0B","PIDs":"10"}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_firing_and_resolved() {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let received = alerts.clone();
+
+        let mut engine = AlertEngine::new()
+            .rule(
+                Rule::new("high-cpu", Condition::CpuAbove(90.0))
+                    .for_duration(Duration::from_secs(30)),
+            )
+            .sink(move |alert: &Alert| received.lock().unwrap().push(alert.state));
+
+        let start = Instant::now();
+        engine.observe_stats_at(&[stats("95.00")], start).unwrap();
+        engine
+            .observe_stats_at(&[stats("95.00")], start + Duration::from_secs(10))
+            .unwrap();
+        assert!(alerts.lock().unwrap().is_empty());
+
+        engine
+            .observe_stats_at(&[stats("95.00")], start + Duration::from_secs(31))
+            .unwrap();
+        engine
+            .observe_stats_at(&[stats("12.00")], start + Duration::from_secs(40))
+            .unwrap();
+
+        assert_eq!(
+            *alerts.lock().unwrap(),
+            vec![AlertState::Firing, AlertState::Resolved]
+        );
+    }
+
+    #[test]
+    fn test_service_from_stats_name() {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let received = alerts.clone();
+
+        let mut engine = AlertEngine::new()
+            .rule(Rule::new("api-cpu", Condition::CpuAbove(90.0)).service("api"))
+            .rule(Rule::new("db-cpu", Condition::CpuAbove(90.0)).service("db"))
+            .sink(move |alert: &Alert| {
+                received
+                    .lock()
+                    .unwrap()
+                    .push((alert.rule.clone(), alert.service.clone()))
+            });
+
+        engine
+            .observe_stats_at(&[stats("95.00")], Instant::now())
+            .unwrap();
+
+        assert_eq!(
+            *alerts.lock().unwrap(),
+            vec![("api-cpu".to_string(), Some("api".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_restarts() {
+        let container = |status: Status| Container {
+            name: "examples-api-1".to_string(),
+            image: "api".to_string(),
+            command: String::new(),
+            service: "api".to_string(),
+            created: SystemTime::now(),
+            status: ContainerStatus {
+                status,
+                since: Duration::ZERO,
+                exit_code: None,
+            },
+            ports: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let received = alerts.clone();
+
+        let window = Duration::from_secs(60);
+        let mut engine = AlertEngine::new()
+            .rule(Rule::new(
+                "flapping",
+                Condition::Restarts { count: 2, window },
+            ))
+            .sink(move |alert: &Alert| received.lock().unwrap().push(alert.value));
+
+        // One restart that is seen restarting and then coming back up
+        let start = Instant::now();
+        for (second, status) in [
+            Status::Up,
+            Status::Restarting,
+            Status::Restarting,
+            Status::Up,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            engine
+                .observe_containers_at(
+                    &[container(status)],
+                    start + Duration::from_secs(second as u64),
+                )
+                .unwrap();
+        }
+        assert!(alerts.lock().unwrap().is_empty());
+
+        // A second restart that fell between two observations
+        for (second, status) in [(10, Status::Exited), (11, Status::Up)] {
+            engine
+                .observe_containers_at(&[container(status)], start + Duration::from_secs(second))
+                .unwrap();
+        }
+        assert_eq!(*alerts.lock().unwrap(), vec![2.0]);
+    }
+}
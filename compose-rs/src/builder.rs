@@ -5,11 +5,17 @@ use std::{
 
 use relative_path::RelativePath;
 
-use crate::{Compose, ComposeBuilderError};
+use crate::{model::ComposeFile, retry::RetryPolicy, Compose, ComposeBuilderError};
 
 #[derive(Default)]
 pub struct ComposeBuilder {
-    path: Option<String>,
+    paths: Vec<String>,
+    model: Option<ComposeFile>,
+    yaml: Option<String>,
+    project_directory: Option<String>,
+    project_name: Option<String>,
+    env_files: Vec<String>,
+    retry: Option<RetryPolicy>,
 }
 
 impl ComposeBuilder {
@@ -20,8 +26,56 @@ impl ComposeBuilder {
 
     /// Set the path to the docker-compose file.
     /// The path can be either absolute or relative.
+    ///
+    /// Can be called multiple times to add override files, later files take precedence.
     pub fn path(mut self, path: impl ToString) -> Self {
-        self.path = Some(path.to_string());
+        self.paths.push(path.to_string());
+        self
+    }
+
+    /// Use a compose file generated from a model, it is written to a temporary file on build.
+    ///
+    /// The model is applied after the files set with [ComposeBuilder::path].
+    pub fn model(mut self, model: impl Into<ComposeFile>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Use an in-memory compose file, it is passed to compose through stdin (`-f -`).
+    ///
+    /// The content is applied after the files set with [ComposeBuilder::path] and
+    /// [ComposeBuilder::model]. Relative paths in it are resolved against the project
+    /// directory, see [ComposeBuilder::project_directory].
+    pub fn yaml(mut self, content: impl ToString) -> Self {
+        self.yaml = Some(content.to_string());
+        self
+    }
+
+    /// Set the directory relative paths like build contexts and bind mounts are resolved
+    /// against, defaults to the directory of the first compose file or the current
+    /// directory for a file passed with [ComposeBuilder::yaml].
+    pub fn project_directory(mut self, path: impl ToString) -> Self {
+        self.project_directory = Some(path.to_string());
+        self
+    }
+
+    /// Set the project name, defaults to the name of the directory of the docker-compose file.
+    pub fn project_name(mut self, name: impl ToString) -> Self {
+        self.project_name = Some(name.to_string());
+        self
+    }
+
+    /// Add an env file to load variables for interpolation from, instead of `.env`.
+    /// Can be called multiple times, later files take precedence.
+    pub fn env_file(mut self, path: impl ToString) -> Self {
+        self.env_files.push(path.to_string());
+        self
+    }
+
+    /// Retry failed commands according to the policy, commands can override it with
+    /// their own `retry`.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
         self
     }
 
@@ -29,31 +83,86 @@ impl ComposeBuilder {
     ///
     /// # Errors
     ///
-    /// Returns a [ComposeBuilderError] if the path is missing or the file is not found.
+    /// Returns a [ComposeBuilderError] if the path is missing, a file is not found
+    /// or the model can't be written.
     pub fn build(self) -> Result<Compose, ComposeBuilderError> {
-        let path = match self.path {
-            Some(path) => path,
-            None => return Err(ComposeBuilderError::MissingField("path".to_string())),
+        if self.paths.is_empty() && self.model.is_none() && self.yaml.is_none() {
+            return Err(ComposeBuilderError::MissingField("path".to_string()));
+        }
+
+        let mut paths = Vec::with_capacity(self.paths.len());
+        for path in self.paths {
+            paths.push(resolve(&path)?);
+        }
+
+        let project_directory = match self.project_directory {
+            Some(path) => Some(resolve(&path)?),
+            None => None,
         };
 
-        let path = match Path::new(&path).is_absolute() {
-            true => PathBuf::from(path),
-            false => {
-                let base = current_dir()?;
-                RelativePath::new(&path).to_logical_path(base)
+        let mut project_name = self.project_name;
+        let generated = match self.model {
+            Some(model) => {
+                let mut file = tempfile::Builder::new()
+                    .prefix("compose-")
+                    .suffix(".yml")
+                    .tempfile()?;
+                serde_yaml::to_writer(&mut file, &model)?;
+
+                let path = file.into_temp_path();
+                // Compose would name the project after the temporary directory, so that
+                // every generated project shares one name. Use the unique file name instead.
+                if paths.is_empty() && project_name.is_none() && model.name.is_none() {
+                    project_name = path
+                        .file_stem()
+                        .map(|stem| project_name_from(&stem.to_string_lossy()));
+                }
+                paths.push(path.to_string_lossy().to_string());
+                Some(path)
             }
+            None => None,
         };
 
-        if path.exists() {
-            Ok(Compose {
-                path: path.to_string_lossy().to_string(),
-            })
-        } else {
-            Err(ComposeBuilderError::FileNotFound(
-                path.to_string_lossy().to_string(),
-            ))
+        Ok(Compose {
+            paths,
+            project_name,
+            env_files: self.env_files,
+            yaml: self.yaml,
+            project_directory,
+            _generated: generated,
+            retry: self.retry,
+        })
+    }
+}
+
+/// Project names may only contain lowercase letters, digits, dashes and underscores.
+fn project_name_from(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '-' | '_' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Resolve a path against the current directory and check that it exists.
+fn resolve(path: &str) -> Result<String, ComposeBuilderError> {
+    let path = match Path::new(path).is_absolute() {
+        true => PathBuf::from(path),
+        false => {
+            let base = current_dir()?;
+            RelativePath::new(path).to_logical_path(base)
         }
+    };
+
+    if !path.exists() {
+        return Err(ComposeBuilderError::FileNotFound(
+            path.to_string_lossy().to_string(),
+        ));
     }
+
+    Ok(path.to_string_lossy().to_string())
 }
 
 #[cfg(test)]
@@ -66,4 +175,43 @@ mod tests {
 
         matches!(compose, Err(ComposeBuilderError::FileNotFound(_)));
     }
+
+    #[test]
+    fn test_compose_from_model() {
+        let spec = crate::model::ComposeSpec::new()
+            .service(crate::model::ServiceSpec::new("web").image("nginx:alpine"));
+        let compose = Compose::from_model(spec).unwrap();
+
+        let path = PathBuf::from(&compose.paths[0]);
+        assert!(compose.model().unwrap().services.contains_key("web"));
+
+        let other = Compose::from_model(crate::model::ComposeSpec::new()).unwrap();
+        assert!(compose.project_name().unwrap().starts_with("compose-"));
+        assert_ne!(compose.project_name(), other.project_name());
+
+        drop(compose);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_compose_from_yaml() {
+        let compose = Compose::builder()
+            .yaml("services:\n  web:\n    image: nginx:alpine\n")
+            .project_directory(".")
+            .build()
+            .unwrap();
+
+        let args = compose
+            .init_command()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert!(args.windows(2).any(|args| args == ["-f", "-"]));
+        assert!(args.contains(&"--project-directory".to_string()));
+        assert_eq!(
+            compose.exec_options().stdin.as_deref(),
+            Some("services:\n  web:\n    image: nginx:alpine\n")
+        );
+        assert!(compose.model().unwrap().services.contains_key("web"));
+    }
 }
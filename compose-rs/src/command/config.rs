@@ -0,0 +1,146 @@
+use indexmap::IndexMap;
+
+use crate::{model::ComposeFile, ComposeCommand, ComposeError};
+
+use super::{exec_options, CatchOutput, ComposeCommandArgs, ExecOptions};
+
+pub enum ConfigArgs {
+    /// Don't interpolate environment variables
+    NoInterpolate,
+    /// Pin image tags to digests
+    ResolveImageDigests,
+}
+
+impl ComposeCommandArgs for ConfigArgs {
+    fn args(&self) -> Vec<String> {
+        match self {
+            ConfigArgs::NoInterpolate => vec!["--no-interpolate".to_string()],
+            ConfigArgs::ResolveImageDigests => vec!["--resolve-image-digests".to_string()],
+        }
+    }
+}
+
+/// Renders the resolved configuration, with interpolation, `extends`, merges of
+/// multiple files and profiles applied.
+pub struct ConfigCommand {
+    command: std::process::Command,
+    options: ExecOptions,
+    args: Vec<ConfigArgs>,
+}
+
+exec_options!(ConfigCommand);
+
+impl ConfigCommand {
+    pub fn new(command: std::process::Command) -> Self {
+        Self {
+            command,
+            options: ExecOptions::default(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn no_interpolate(mut self) -> Self {
+        self.args.push(ConfigArgs::NoInterpolate);
+        self
+    }
+
+    pub fn resolve_image_digests(mut self) -> Self {
+        self.args.push(ConfigArgs::ResolveImageDigests);
+        self
+    }
+
+    /// List the names of the services, runs `config --services`.
+    pub fn services(self) -> Result<Vec<String>, ComposeError> {
+        self.lines(&["--services"])
+    }
+
+    /// List the names of the volumes, runs `config --volumes`.
+    pub fn volumes(self) -> Result<Vec<String>, ComposeError> {
+        self.lines(&["--volumes"])
+    }
+
+    /// List the images used by the services, runs `config --images`.
+    pub fn images(self) -> Result<Vec<String>, ComposeError> {
+        self.lines(&["--images"])
+    }
+
+    /// The configuration hash of each service, runs `config --hash`.
+    ///
+    /// If `services` is empty the hashes of all services are returned.
+    pub fn hashes(self, services: &[&str]) -> Result<IndexMap<String, String>, ComposeError> {
+        let services = match services.is_empty() {
+            true => "*".to_string(),
+            false => services.join(","),
+        };
+
+        self.lines(&["--hash", &services])?
+            .into_iter()
+            .map(|line| match line.split_once(' ') {
+                Some((service, hash)) => Ok((service.to_string(), hash.trim().to_string())),
+                None => Err(ComposeError::ParseError(format!(
+                    "Invalid config hash line: {line}"
+                ))),
+            })
+            .collect()
+    }
+
+    fn lines(self, flags: &[&str]) -> Result<Vec<String>, ComposeError> {
+        let mut command = self.command;
+        command.arg(Self::COMMAND).args(flags);
+
+        for arg in self.args {
+            command.args(arg.args());
+        }
+
+        let output = command.catch_output_with(&self.options)?;
+        let output = String::from_utf8_lossy(&output.stdout);
+
+        Ok(output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+impl ComposeCommand<ComposeFile, ConfigArgs> for ConfigCommand {
+    const COMMAND: &'static str = "config";
+
+    fn exec(self) -> Result<ComposeFile, ComposeError> {
+        let mut command = self.command;
+        command.arg(Self::COMMAND).arg("--format").arg("json");
+
+        for arg in self.args {
+            command.args(arg.args());
+        }
+
+        let output = command.catch_output_with(&self.options)?;
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolved_config() {
+        let config: ComposeFile = serde_json::from_str(
+            r#"{"name":"examples","services":{"rqlite":{"command":null,"depends_on":{"db":{"condition":"service_started","required":true}},"deploy":{"resources":{"limits":{"cpus":0.5,"memory":"536870912"}}},"entrypoint":null,"environment":{"A":"b","B":null},"image":"rqlite/rqlite:8.22.2","networks":{"default":null},"ports":[{"mode":"ingress","target":4001,"published":"4001","protocol":"tcp"}],"scale":2,"volumes":[{"type":"bind","source":"/tmp/data","target":"/data","bind":{"create_host_path":true}}]}},"networks":{"default":{"name":"examples_default","ipam":{}}}}"#,
+        )
+        .unwrap();
+
+        let rqlite = &config.services["rqlite"];
+        assert_eq!(config.name.as_deref(), Some("examples"));
+        assert_eq!(rqlite.scale, Some(2));
+        assert_eq!(rqlite.ports[0].published.as_deref(), Some("4001"));
+        assert_eq!(rqlite.environment["B"], None);
+        assert_eq!(rqlite.volumes[0].source.as_deref(), Some("/tmp/data"));
+        assert_eq!(
+            config.networks["default"].name.as_deref(),
+            Some("examples_default")
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use super::{CatchOutput, ComposeCommandArgs};
+use super::{exec_options, CatchOutput, ComposeCommandArgs, ExecOptions};
 use crate::{ComposeCommand, ComposeError};
 
 pub enum RemoveOptions {
@@ -13,6 +13,8 @@ pub enum DownArgs {
     RemoveOrphans,
     RemoveImages(RemoveOptions),
     Timeout(Duration),
+    /// Print the actions without executing them
+    DryRun,
 }
 
 impl ComposeCommandArgs for DownArgs {
@@ -27,19 +29,24 @@ impl ComposeCommandArgs for DownArgs {
             DownArgs::Timeout(duration) => {
                 vec!["--timeout".to_string(), duration.as_secs().to_string()]
             }
+            DownArgs::DryRun => vec!["--dry-run".to_string()],
         }
     }
 }
 
 pub struct DownCommand {
     command: std::process::Command,
+    options: ExecOptions,
     args: Vec<DownArgs>,
 }
 
+exec_options!(DownCommand, exec_timeout);
+
 impl DownCommand {
     pub fn new(command: std::process::Command) -> Self {
         Self {
             command,
+            options: ExecOptions::default(),
             args: Vec::new(),
         }
     }
@@ -59,10 +66,16 @@ impl DownCommand {
         self
     }
 
+    /// The time containers get to stop before they are killed, passed as `--timeout`.
     pub fn timeout(mut self, duration: Duration) -> Self {
         self.args.push(DownArgs::Timeout(duration));
         self
     }
+
+    pub fn dry_run(mut self) -> Self {
+        self.args.push(DownArgs::DryRun);
+        self
+    }
 }
 
 impl ComposeCommand<(), DownArgs> for DownCommand {
@@ -73,10 +86,10 @@ impl ComposeCommand<(), DownArgs> for DownCommand {
         command.arg(Self::COMMAND);
 
         for arg in self.args {
-            command.args(&arg.args());
+            command.args(arg.args());
         }
 
-        command.output().catch_output()?;
+        command.catch_output_with(&self.options)?;
 
         Ok(())
     }
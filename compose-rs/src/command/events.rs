@@ -0,0 +1,154 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{parser, ComposeError};
+
+use super::{exec, exec_options, ExecOptions};
+
+//{"action":"start","attributes":{"image":"rqlite/rqlite:8.22.2","name":"examples-rqlite-1"},"id":"9ca40acb565a...","service":"rqlite","time":"2024-04-20T14:59:44.123456789+02:00","type":"container"}
+
+/// The action of a [ComposeEvent].
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum EventAction {
+    Create,
+    Start,
+    Restart,
+    Stop,
+    Die,
+    Kill,
+    Oom,
+    Destroy,
+    Pause,
+    Unpause,
+    /// A health check changed the container's health, e.g. `healthy` or `unhealthy`
+    HealthStatus(String),
+    /// Any action not covered by the other variants
+    Other(String),
+}
+
+impl EventAction {
+    fn from_string(action: &str) -> Self {
+        if let Some(status) = action.strip_prefix("health_status") {
+            return Self::HealthStatus(status.trim_start_matches(':').trim().to_string());
+        }
+
+        match action {
+            "create" => Self::Create,
+            "start" => Self::Start,
+            "restart" => Self::Restart,
+            "stop" => Self::Stop,
+            "die" => Self::Die,
+            "kill" => Self::Kill,
+            "oom" => Self::Oom,
+            "destroy" => Self::Destroy,
+            "pause" => Self::Pause,
+            "unpause" => Self::Unpause,
+            _ => Self::Other(action.to_string()),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for EventAction {
+    fn deserialize<D>(deserializer: D) -> Result<EventAction, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let action = String::deserialize(deserializer)?;
+        Ok(EventAction::from_string(&action))
+    }
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let time = String::deserialize(deserializer)?;
+    parser::parse_rfc3339(&time).map_err(serde::de::Error::custom)
+}
+
+/// An event emitted by `docker compose events`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ComposeEvent {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub time: SystemTime,
+    pub service: String,
+    /// The ID of the container the event belongs to
+    #[serde(rename = "id")]
+    pub container: String,
+    pub action: EventAction,
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
+pub type EventIterator = Box<dyn Iterator<Item = Result<ComposeEvent, ComposeError>> + Send>;
+
+pub struct EventsCommand {
+    command: std::process::Command,
+    options: ExecOptions,
+    services: Vec<String>,
+}
+
+exec_options!(stream EventsCommand);
+
+impl EventsCommand {
+    pub fn new(command: std::process::Command) -> Self {
+        Self {
+            command,
+            options: ExecOptions::default(),
+            services: Vec::new(),
+        }
+    }
+
+    /// Only receive events of the given service, can be called multiple times.
+    pub fn service(mut self, service: &str) -> Self {
+        self.services.push(service.to_string());
+        self
+    }
+
+    /// Stream events until the compose process exits.
+    ///
+    /// The stream ends with [ComposeError::Timeout] or [ComposeError::Cancelled] once the
+    /// timeout expires or the token is cancelled.
+    pub fn stream(self) -> Result<EventIterator, ComposeError> {
+        let mut command = self.command;
+
+        command.arg("events").arg("--json").args(&self.services);
+
+        let events = exec::stream(&mut command, &self.options)?
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| {
+                let line = parser::remove_ansi_codes(&line?)?;
+                Ok(serde_json::from_str(line.trim())?)
+            });
+
+        Ok(Box::new(events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_event() {
+        let event: ComposeEvent = serde_json::from_str(
+            r#"{"action":"health_status: unhealthy","attributes":{"image":"rqlite/rqlite:8.22.2","name":"examples-rqlite-1"},"id":"9ca40acb565a","service":"rqlite","time":"2024-04-20T14:59:44.5+02:00","type":"container"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(event.service, "rqlite");
+        assert_eq!(event.container, "9ca40acb565a");
+        assert_eq!(
+            event.action,
+            EventAction::HealthStatus("unhealthy".to_string())
+        );
+        assert_eq!(event.attributes["name"], "examples-rqlite-1");
+        assert_eq!(
+            event.time,
+            UNIX_EPOCH + Duration::from_millis(1_713_617_984_500)
+        );
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    io::{BufRead, BufReader, Lines, Read, Write},
+    process::{Child, ChildStdout, Command, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{error::CommandFailure, retry::RetryPolicy, ComposeError};
+
+/// How often a running command is checked for its deadline and cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Cancels the commands it is passed to, it can be cloned and shared across threads
+/// and calls.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Kill the running commands and make further commands fail immediately.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// A line a running command printed, without the line ending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+pub(crate) type OutputCallback = Arc<dyn Fn(&OutputLine) + Send + Sync>;
+
+/// Where the lines of a running command are sent as they are printed.
+#[derive(Clone)]
+pub(crate) struct OutputSink(OutputCallback);
+
+impl OutputSink {
+    pub(crate) fn callback<F>(callback: F) -> Self
+    where
+        F: Fn(&OutputLine) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+
+    pub(crate) fn send(&self, line: &OutputLine) {
+        (self.0)(line)
+    }
+
+    /// Send the lines to a channel, lines are dropped once the receiver is gone.
+    pub(crate) fn channel(sender: mpsc::Sender<OutputLine>) -> Self {
+        Self::callback(move |line| {
+            let _ = sender.send(line.clone());
+        })
+    }
+}
+
+impl std::fmt::Debug for OutputSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OutputSink")
+    }
+}
+
+/// How a command is run.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExecOptions {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) output: Option<OutputSink>,
+    /// Written to the stdin of the command, e.g. a compose file passed with `-f -`
+    pub(crate) stdin: Option<Arc<str>>,
+}
+
+impl ExecOptions {
+    /// The options of a `docker` command run as a step of the command started at `start`,
+    /// it gets the time that is left of the timeout. The compose file on stdin and the
+    /// output sink only belong to the compose command.
+    pub(crate) fn step(&self, start: Instant) -> Self {
+        Self {
+            timeout: self
+                .timeout
+                .map(|timeout| timeout.saturating_sub(start.elapsed())),
+            cancellation: self.cancellation.clone(),
+            retry: self.retry.clone(),
+            output: None,
+            stdin: None,
+        }
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.timeout.is_some() || self.cancellation.is_some()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// The error of a command that was killed on its deadline or by its token.
+    fn interrupted(&self, timed_out: bool, failure: CommandFailure) -> ComposeError {
+        match (timed_out, self.timeout) {
+            (true, Some(timeout)) => ComposeError::Timeout { timeout, failure },
+            _ => ComposeError::Cancelled(failure),
+        }
+    }
+
+    /// Let a bounded command lead its own process group, so it can be killed as a whole.
+    fn prepare(&self, command: &mut Command) {
+        #[cfg(unix)]
+        if self.is_bounded() {
+            use std::os::unix::process::CommandExt;
+            // The compose plugin runs as a child of the docker CLI, both have to be killed
+            command.process_group(0);
+        }
+        #[cfg(not(unix))]
+        let _ = command;
+    }
+}
+
+/// Spawn a command, writing [ExecOptions::stdin] to it from a thread so the content can
+/// exceed the pipe buffer.
+pub(crate) fn spawn(command: &mut Command, options: &ExecOptions) -> Result<Child, ComposeError> {
+    if options.stdin.is_some() {
+        command.stdin(Stdio::piped());
+    }
+
+    let mut child = command.spawn()?;
+    if let (Some(content), Some(mut stdin)) = (options.stdin.clone(), child.stdin.take()) {
+        // A command that exits without reading all of it reports its own error
+        thread::spawn(move || stdin.write_all(content.as_bytes()));
+    }
+
+    Ok(child)
+}
+
+/// Run a command to completion, killing its process group once the timeout expires or
+/// the token is cancelled, and sending its lines to the output sink as they are printed.
+pub(crate) fn run(command: &mut Command, options: &ExecOptions) -> Result<Output, ComposeError> {
+    let bounded = options.is_bounded();
+    if !bounded && options.output.is_none() && options.stdin.is_none() {
+        return Ok(command.output()?);
+    }
+
+    let argv = CommandFailure::argv(command);
+    let failure = |stdout: &Mutex<Vec<u8>>, stderr: &Mutex<Vec<u8>>| CommandFailure {
+        argv: argv.clone(),
+        exit_code: None,
+        stdout: String::from_utf8_lossy(&stdout.lock().unwrap()).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr.lock().unwrap()).into_owned(),
+    };
+
+    if options.is_cancelled() {
+        return Err(ComposeError::Cancelled(failure(
+            &Mutex::default(),
+            &Mutex::default(),
+        )));
+    }
+
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    options.prepare(command);
+
+    let mut child = spawn(command, options)?;
+    let sink = options.output.as_ref();
+    let (stdout, stdout_reader) = capture(child.stdout.take(), OutputStream::Stdout, sink);
+    let (stderr, stderr_reader) = capture(child.stderr.take(), OutputStream::Stderr, sink);
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+
+    loop {
+        let status = match bounded {
+            true => child.try_wait()?,
+            false => Some(child.wait()?),
+        };
+        if let Some(status) = status {
+            let _ = stdout_reader.join();
+            let _ = stderr_reader.join();
+            return Ok(Output {
+                status,
+                stdout: std::mem::take(&mut stdout.lock().unwrap()),
+                stderr: std::mem::take(&mut stderr.lock().unwrap()),
+            });
+        }
+
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if timed_out || options.is_cancelled() {
+            kill(&mut child);
+            let _ = child.wait();
+
+            // The readers are not joined, a process outside the group may keep the pipes open
+            return Err(options.interrupted(timed_out, failure(&stdout, &stderr)));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The stdout of a streaming command, line by line.
+///
+/// Once the timeout expires or the token is cancelled the process group is killed and
+/// the lines end with [ComposeError::Timeout] or [ComposeError::Cancelled].
+pub(crate) struct StreamLines {
+    lines: Lines<BufReader<ChildStdout>>,
+    interrupted: Arc<Mutex<Option<ComposeError>>>,
+}
+
+impl Iterator for StreamLines {
+    type Item = Result<String, ComposeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lines.next() {
+            Some(line) => Some(line.map_err(ComposeError::from)),
+            None => self.interrupted.lock().unwrap().take().map(Err),
+        }
+    }
+}
+
+/// Spawn a command that runs until it is stopped, like `events`, and read its stdout.
+pub(crate) fn stream(
+    command: &mut Command,
+    options: &ExecOptions,
+) -> Result<StreamLines, ComposeError> {
+    let failure = CommandFailure {
+        argv: CommandFailure::argv(command),
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+
+    if options.is_cancelled() {
+        return Err(ComposeError::Cancelled(failure));
+    }
+
+    command.stdout(Stdio::piped());
+    options.prepare(command);
+
+    let mut child = spawn(command, options)?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| ComposeError::IoError(std::io::Error::other("Failed to open stdout")))?;
+
+    let interrupted = Arc::new(Mutex::new(None));
+    if options.is_bounded() {
+        let options = options.clone();
+        let reason = interrupted.clone();
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+
+        thread::spawn(move || {
+            while let Ok(None) = child.try_wait() {
+                let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                if timed_out || options.is_cancelled() {
+                    // Set before the kill, so the reader finds it once stdout is closed
+                    *reason.lock().unwrap() = Some(options.interrupted(timed_out, failure));
+                    kill(&mut child);
+                    let _ = child.wait();
+                    return;
+                }
+
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    }
+
+    Ok(StreamLines {
+        lines: BufReader::new(stdout).lines(),
+        interrupted,
+    })
+}
+
+/// Read a pipe to the end on a thread, the buffer holds what has been read so far.
+fn capture<R>(
+    pipe: Option<R>,
+    stream: OutputStream,
+    sink: Option<&OutputSink>,
+) -> (Arc<Mutex<Vec<u8>>>, thread::JoinHandle<()>)
+where
+    R: Read + Send + 'static,
+{
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let captured = buffer.clone();
+    let sink = sink.cloned();
+
+    let reader = thread::spawn(move || {
+        let Some(pipe) = pipe else {
+            return;
+        };
+        let mut pipe = BufReader::new(pipe);
+        let mut line = Vec::new();
+        while let Ok(read @ 1..) = pipe.read_until(b'\n', &mut line) {
+            captured.lock().unwrap().extend_from_slice(&line[..read]);
+
+            if let Some(OutputSink(callback)) = &sink {
+                let text = String::from_utf8_lossy(&line);
+                callback(&OutputLine {
+                    stream,
+                    line: text.trim_end_matches(['\n', '\r']).to_string(),
+                });
+            }
+            line.clear();
+        }
+    });
+
+    (buffer, reader)
+}
+
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: kill has no memory safety requirements, the child leads its own process group
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_kills_process_group() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo started; sleep 5 & wait"]);
+
+        let options = ExecOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let error = run(&mut command, &options).unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let ComposeError::Timeout { timeout, failure } = error else {
+            panic!("unexpected error {error:?}");
+        };
+        assert_eq!(timeout, Duration::from_millis(200));
+        assert_eq!(failure.stdout, "started\n");
+        assert_eq!(failure.argv[0], "sh");
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let token = CancellationToken::new();
+        let options = ExecOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        });
+
+        let error = run(Command::new("sleep").arg("5"), &options).unwrap_err();
+        assert!(matches!(error, ComposeError::Cancelled(_)));
+        canceller.join().unwrap();
+
+        // A cancelled token fails further commands before they start
+        let error = run(&mut Command::new("true"), &options).unwrap_err();
+        assert!(matches!(error, ComposeError::Cancelled(_)));
+
+        let output = run(
+            Command::new("echo").arg("done"),
+            &ExecOptions {
+                timeout: Some(Duration::from_secs(5)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(output.stdout, b"done\n");
+    }
+
+    #[test]
+    fn test_output_lines() {
+        let (sender, receiver) = mpsc::channel();
+        let options = ExecOptions {
+            output: Some(OutputSink::channel(sender)),
+            ..Default::default()
+        };
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo pulling; echo warning >&2; printf done"]);
+
+        let output = run(&mut command, &options).unwrap();
+        assert_eq!(output.stdout, b"pulling\ndone");
+        assert_eq!(output.stderr, b"warning\n");
+
+        let mut lines = receiver.try_iter().collect::<Vec<_>>();
+        lines.sort_by_key(|line| line.stream == OutputStream::Stderr);
+        assert_eq!(
+            lines,
+            [
+                OutputLine {
+                    stream: OutputStream::Stdout,
+                    line: "pulling".to_string()
+                },
+                OutputLine {
+                    stream: OutputStream::Stdout,
+                    line: "done".to_string()
+                },
+                OutputLine {
+                    stream: OutputStream::Stderr,
+                    line: "warning".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stdin() {
+        // Larger than a pipe buffer, so it has to be written while the command runs
+        let content = "services: {}\n".repeat(20_000);
+        let options = ExecOptions {
+            stdin: Some(Arc::from(content.as_str())),
+            ..Default::default()
+        };
+
+        let output = run(&mut Command::new("cat"), &options).unwrap();
+        assert_eq!(output.stdout, content.as_bytes());
+
+        // Each run gets its own pipe
+        let output = run(Command::new("wc").arg("-c"), &options).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            content.len().to_string()
+        );
+    }
+
+    #[test]
+    fn test_stream_timeout() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo first; sleep 5 & wait"]);
+
+        let options = ExecOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let mut lines = stream(&mut command, &options).unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "first");
+        assert!(matches!(
+            lines.next(),
+            Some(Err(ComposeError::Timeout { .. }))
+        ));
+        assert!(lines.next().is_none());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,583 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant, SystemTime},
+};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{parser, ComposeCommand, ComposeError};
+
+use super::{docker, exec_options, CatchOutput, ExecOptions};
+
+const SERVICE_LABEL: &str = "com.docker.compose.service";
+const PROJECT_LABEL: &str = "com.docker.compose.project";
+const CONFIG_HASH_LABEL: &str = "com.docker.compose.config-hash";
+const NUMBER_LABEL: &str = "com.docker.compose.container-number";
+const ONEOFF_LABEL: &str = "com.docker.compose.oneoff";
+
+/// The details of a container reported by `docker inspect`.
+#[derive(Serialize, Debug, Clone)]
+pub struct ContainerDetails {
+    pub id: String,
+    pub name: String,
+    pub created: SystemTime,
+    /// The ID of the local image, e.g. `sha256:4f...`
+    pub image_id: String,
+    /// The image reference the container was created from, e.g. `nginx:alpine`
+    pub image: String,
+    /// The repository digest of the image, e.g. `nginx@sha256:a4...`, `None` for images
+    /// that were built locally and never pushed or pulled
+    pub image_digest: Option<String>,
+    pub labels: HashMap<String, String>,
+    pub env: IndexMap<String, String>,
+    pub mounts: Vec<Mount>,
+    /// The networks the container is attached to, by network name
+    pub networks: HashMap<String, NetworkEndpoint>,
+    pub restart_count: u32,
+    pub state: ContainerState,
+    pub resources: HostResources,
+}
+
+impl ContainerDetails {
+    /// The compose service of the container.
+    pub fn service(&self) -> Option<&str> {
+        self.labels.get(SERVICE_LABEL).map(String::as_str)
+    }
+
+    /// The compose project of the container.
+    pub fn project(&self) -> Option<&str> {
+        self.labels.get(PROJECT_LABEL).map(String::as_str)
+    }
+
+    /// The hash of the service configuration the container was created from.
+    pub fn config_hash(&self) -> Option<&str> {
+        self.labels.get(CONFIG_HASH_LABEL).map(String::as_str)
+    }
+
+    /// The replica number of the container within its service.
+    pub fn number(&self) -> Option<u32> {
+        self.labels.get(NUMBER_LABEL)?.parse().ok()
+    }
+
+    /// How long the container has been running, from its exact start time.
+    pub fn uptime(&self) -> Option<Duration> {
+        if !self.state.running {
+            return None;
+        }
+        SystemTime::now()
+            .duration_since(self.state.started_at?)
+            .ok()
+    }
+
+    /// Whether the container was created by `run` rather than `up`.
+    pub fn is_oneoff(&self) -> bool {
+        self.labels.get(ONEOFF_LABEL).is_some_and(|v| v == "True")
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Mount {
+    /// `bind`, `volume` or `tmpfs`
+    #[serde(rename = "Type")]
+    pub kind: String,
+    /// The name of a named volume
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub source: String,
+    pub destination: String,
+    #[serde(default)]
+    pub mode: String,
+    #[serde(rename = "RW")]
+    pub read_write: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct NetworkEndpoint {
+    pub aliases: Vec<String>,
+    pub ip_address: Option<IpAddr>,
+    pub ipv6_address: Option<IpAddr>,
+    pub gateway: Option<IpAddr>,
+    pub mac_address: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ContainerState {
+    /// e.g. `running`, `exited` or `restarting`
+    pub status: String,
+    pub running: bool,
+    pub paused: bool,
+    pub restarting: bool,
+    pub oom_killed: bool,
+    pub dead: bool,
+    pub pid: u32,
+    pub exit_code: i64,
+    pub error: Option<String>,
+    pub started_at: Option<SystemTime>,
+    pub finished_at: Option<SystemTime>,
+    pub health: Option<Health>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Health {
+    /// `starting`, `healthy` or `unhealthy`
+    pub status: String,
+    pub failing_streak: u32,
+    #[serde(default, deserialize_with = "nullable_vec")]
+    pub log: Vec<HealthLog>,
+}
+
+/// The result of a single healthcheck run.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct HealthLog {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: SystemTime,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: SystemTime,
+    pub exit_code: i64,
+    pub output: String,
+}
+
+/// The resource limits of a container, unset limits are `None`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct HostResources {
+    /// Memory limit in bytes
+    pub memory: Option<u64>,
+    /// Memory reservation in bytes
+    pub memory_reservation: Option<u64>,
+    /// Memory plus swap limit in bytes, `-1` for unlimited swap
+    pub memory_swap: Option<i64>,
+    /// Fraction of CPUs
+    pub cpus: Option<f64>,
+    pub cpu_shares: Option<u64>,
+    pub pids_limit: Option<i64>,
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let time = String::deserialize(deserializer)?;
+    parser::parse_rfc3339(&time).map_err(serde::de::Error::custom)
+}
+
+/// Docker reports unset times as `0001-01-01T00:00:00Z`.
+fn deserialize_optional_time<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(time) if !time.is_empty() && !time.starts_with("0001-") => {
+            parser::parse_rfc3339(&time)
+                .map(Some)
+                .map_err(serde::de::Error::custom)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn nullable_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawContainer {
+    id: String,
+    name: String,
+    #[serde(deserialize_with = "deserialize_time")]
+    created: SystemTime,
+    image: String,
+    #[serde(default)]
+    restart_count: u32,
+    state: RawState,
+    #[serde(default, deserialize_with = "nullable_vec")]
+    mounts: Vec<Mount>,
+    config: RawConfig,
+    #[serde(default)]
+    host_config: RawHostConfig,
+    network_settings: RawNetworkSettings,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawState {
+    status: String,
+    running: bool,
+    paused: bool,
+    restarting: bool,
+    #[serde(rename = "OOMKilled")]
+    oom_killed: bool,
+    dead: bool,
+    pid: u32,
+    exit_code: i64,
+    #[serde(default)]
+    error: String,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    started_at: Option<SystemTime>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    finished_at: Option<SystemTime>,
+    #[serde(default)]
+    health: Option<Health>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawConfig {
+    image: String,
+    #[serde(default, deserialize_with = "nullable_vec")]
+    env: Vec<String>,
+    #[serde(default)]
+    labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+struct RawHostConfig {
+    #[serde(default)]
+    memory: i64,
+    #[serde(default)]
+    memory_reservation: i64,
+    #[serde(default)]
+    memory_swap: i64,
+    #[serde(default)]
+    nano_cpus: i64,
+    #[serde(default)]
+    cpu_shares: i64,
+    #[serde(default)]
+    pids_limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawNetworkSettings {
+    #[serde(default)]
+    networks: Option<HashMap<String, RawEndpoint>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawEndpoint {
+    #[serde(default, deserialize_with = "nullable_vec")]
+    aliases: Vec<String>,
+    #[serde(default, rename = "IPAddress")]
+    ip_address: String,
+    #[serde(default, rename = "GlobalIPv6Address")]
+    ipv6_address: String,
+    #[serde(default)]
+    gateway: String,
+    #[serde(default)]
+    mac_address: String,
+}
+
+impl From<RawContainer> for ContainerDetails {
+    fn from(raw: RawContainer) -> Self {
+        let positive = |value: i64| u64::try_from(value).ok().filter(|v| *v > 0);
+        let non_empty = |value: String| (!value.is_empty()).then_some(value);
+
+        Self {
+            id: raw.id,
+            name: raw.name.trim_start_matches('/').to_string(),
+            created: raw.created,
+            image_id: raw.image,
+            image: raw.config.image,
+            image_digest: None,
+            labels: raw.config.labels.unwrap_or_default(),
+            env: raw
+                .config
+                .env
+                .into_iter()
+                .map(|entry| match entry.split_once('=') {
+                    Some((key, value)) => (key.to_string(), value.to_string()),
+                    None => (entry, String::new()),
+                })
+                .collect(),
+            mounts: raw.mounts,
+            networks: raw
+                .network_settings
+                .networks
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, endpoint)| {
+                    let endpoint = NetworkEndpoint {
+                        aliases: endpoint.aliases,
+                        ip_address: endpoint.ip_address.parse().ok(),
+                        ipv6_address: endpoint.ipv6_address.parse().ok(),
+                        gateway: endpoint.gateway.parse().ok(),
+                        mac_address: non_empty(endpoint.mac_address),
+                    };
+                    (name, endpoint)
+                })
+                .collect(),
+            restart_count: raw.restart_count,
+            state: ContainerState {
+                status: raw.state.status,
+                running: raw.state.running,
+                paused: raw.state.paused,
+                restarting: raw.state.restarting,
+                oom_killed: raw.state.oom_killed,
+                dead: raw.state.dead,
+                pid: raw.state.pid,
+                exit_code: raw.state.exit_code,
+                error: non_empty(raw.state.error),
+                started_at: raw.state.started_at,
+                finished_at: raw.state.finished_at,
+                health: raw.state.health,
+            },
+            resources: HostResources {
+                memory: positive(raw.host_config.memory),
+                memory_reservation: positive(raw.host_config.memory_reservation),
+                memory_swap: (raw.host_config.memory_swap != 0)
+                    .then_some(raw.host_config.memory_swap),
+                cpus: (raw.host_config.nano_cpus > 0)
+                    .then(|| raw.host_config.nano_cpus as f64 / 1e9),
+                cpu_shares: positive(raw.host_config.cpu_shares),
+                pids_limit: raw.host_config.pids_limit.filter(|limit| *limit > 0),
+            },
+        }
+    }
+}
+
+/// Parse the JSON output of `docker inspect`.
+pub(crate) fn parse_inspect(output: &[u8]) -> Result<Vec<ContainerDetails>, ComposeError> {
+    let containers: Vec<RawContainer> = serde_json::from_slice(output)?;
+    Ok(containers.into_iter().map(ContainerDetails::from).collect())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawImage {
+    id: String,
+    #[serde(default, deserialize_with = "nullable_vec")]
+    repo_digests: Vec<String>,
+}
+
+/// Parse the JSON output of `docker image inspect` into the repository digests by image ID.
+fn parse_image_digests(output: &[u8]) -> Result<HashMap<String, Vec<String>>, ComposeError> {
+    let images: Vec<RawImage> = serde_json::from_slice(output)?;
+    Ok(images
+        .into_iter()
+        .map(|image| (image.id, image.repo_digests))
+        .collect())
+}
+
+/// The repository of an image reference, without its tag or digest.
+fn repository(reference: &str) -> &str {
+    let reference = reference.split('@').next().unwrap_or(reference);
+    match reference.rsplit_once(':') {
+        // A colon before the last slash separates a registry port, not a tag
+        Some((repository, tag)) if !tag.contains('/') => repository,
+        _ => reference,
+    }
+}
+
+/// The digest of the repository the container's image was pulled from, or the first
+/// one if the image is known under several repositories.
+fn image_digest(image: &str, digests: &[String]) -> Option<String> {
+    digests
+        .iter()
+        .find(|digest| repository(digest) == repository(image))
+        .or(digests.first())
+        .cloned()
+}
+
+/// Fill in the image digests of the containers with `docker image inspect`.
+fn resolve_image_digests(
+    containers: &mut [ContainerDetails],
+    options: &ExecOptions,
+) -> Result<(), ComposeError> {
+    let mut ids = containers
+        .iter()
+        .map(|container| container.image_id.as_str())
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let args = ["image", "inspect"].into_iter().chain(ids);
+    let output = docker(args, options)?;
+    let digests = parse_image_digests(&output.stdout)?;
+
+    for container in containers {
+        if let Some(repo_digests) = digests.get(&container.image_id) {
+            container.image_digest = image_digest(&container.image, repo_digests);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `docker inspect` on the given container IDs.
+pub(crate) fn inspect_containers(
+    ids: &[String],
+    options: &ExecOptions,
+) -> Result<Vec<ContainerDetails>, ComposeError> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let args = std::iter::once("inspect").chain(ids.iter().map(String::as_str));
+    let output = docker(args, options)?;
+
+    parse_inspect(&output.stdout)
+}
+
+/// Inspects the containers of the project with `docker inspect`.
+///
+/// The containers are listed with `docker compose ps -q` first, which is why
+/// [ComposeCommand::COMMAND] is `ps`, and their images are looked up with
+/// `docker image inspect` for the image digests.
+pub struct InspectCommand {
+    command: std::process::Command,
+    options: ExecOptions,
+    services: Vec<String>,
+}
+
+exec_options!(InspectCommand);
+
+impl InspectCommand {
+    pub fn new(command: std::process::Command) -> Self {
+        Self {
+            command,
+            options: ExecOptions::default(),
+            services: Vec::new(),
+        }
+    }
+
+    /// Only inspect the containers of the given service, can be called multiple times.
+    pub fn service(mut self, service: &str) -> Self {
+        self.services.push(service.to_string());
+        self
+    }
+}
+
+impl ComposeCommand<Vec<ContainerDetails>> for InspectCommand {
+    /// The compose command that lists the containers, they are inspected with `docker`
+    const COMMAND: &'static str = "ps";
+
+    fn exec(self) -> Result<Vec<ContainerDetails>, ComposeError> {
+        let start = Instant::now();
+        let mut command = self.command;
+        command
+            .arg(Self::COMMAND)
+            .args(["-a", "-q", "--no-trunc"])
+            .args(&self.services);
+
+        let output = command.catch_output_with(&self.options)?;
+        let ids = String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        let mut containers = inspect_containers(&ids, &self.options.step(start))?;
+        resolve_image_digests(&mut containers, &self.options.step(start))?;
+
+        Ok(containers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_inspect() {
+        let output = r#"[{
+            "Id": "9ca40acb565a",
+            "Created": "2024-04-20T12:59:40.5Z",
+            "State": {
+                "Status": "running", "Running": true, "Paused": false, "Restarting": false,
+                "OOMKilled": false, "Dead": false, "Pid": 4242, "ExitCode": 0, "Error": "",
+                "StartedAt": "2024-04-20T12:59:44Z", "FinishedAt": "0001-01-01T00:00:00Z",
+                "Health": {"Status": "healthy", "FailingStreak": 0, "Log": [
+                    {"Start": "2024-04-20T14:59:50.1+02:00", "End": "2024-04-20T14:59:50.2+02:00", "ExitCode": 0, "Output": "ok"}
+                ]}
+            },
+            "Image": "sha256:4f0c",
+            "Name": "/examples-rqlite-1",
+            "RestartCount": 2,
+            "Mounts": [{"Type": "volume", "Name": "data", "Source": "/var/lib/docker/volumes/data/_data", "Destination": "/data", "Driver": "local", "Mode": "z", "RW": true, "Propagation": ""}],
+            "Config": {
+                "Image": "rqlite/rqlite:8.22.2",
+                "Env": ["A=b", "PATH=/usr/bin:/bin", "EMPTY"],
+                "Labels": {"com.docker.compose.service": "rqlite", "com.docker.compose.container-number": "1", "com.docker.compose.config-hash": "abc"}
+            },
+            "HostConfig": {"Memory": 536870912, "MemoryReservation": 0, "MemorySwap": -1, "NanoCpus": 500000000, "CpuShares": 0, "PidsLimit": null},
+            "NetworkSettings": {"Networks": {"examples_default": {"Aliases": ["rqlite"], "IPAddress": "172.18.0.2", "GlobalIPv6Address": "", "Gateway": "172.18.0.1", "MacAddress": "02:42:ac:12:00:02"}}}
+        }]"#;
+
+        let containers = parse_inspect(output.as_bytes()).unwrap();
+        let container = &containers[0];
+
+        assert_eq!(container.name, "examples-rqlite-1");
+        assert_eq!(container.service(), Some("rqlite"));
+        assert_eq!(container.number(), Some(1));
+        assert_eq!(container.config_hash(), Some("abc"));
+        assert_eq!(
+            container.created,
+            UNIX_EPOCH + Duration::from_millis(1_713_617_980_500)
+        );
+        assert_eq!(container.env["PATH"], "/usr/bin:/bin");
+        assert_eq!(container.env["EMPTY"], "");
+        assert_eq!(container.mounts[0].name.as_deref(), Some("data"));
+        assert_eq!(container.restart_count, 2);
+
+        let network = &container.networks["examples_default"];
+        assert_eq!(network.ip_address, Some("172.18.0.2".parse().unwrap()));
+        assert_eq!(network.ipv6_address, None);
+
+        assert!(container.state.started_at.is_some());
+        assert!(container.state.finished_at.is_none());
+        let health = container.state.health.as_ref().unwrap();
+        assert_eq!(health.log[0].output, "ok");
+        assert_eq!(
+            health.log[0].start,
+            UNIX_EPOCH + Duration::from_millis(1_713_617_990_100)
+        );
+
+        assert_eq!(container.resources.memory, Some(536_870_912));
+        assert_eq!(container.resources.memory_swap, Some(-1));
+        assert_eq!(container.resources.cpus, Some(0.5));
+        assert_eq!(container.resources.pids_limit, None);
+    }
+
+    #[test]
+    fn test_image_digests() {
+        let output = r#"[
+            {"Id": "sha256:4f0c", "RepoDigests": ["mirror.local:5000/nginx@sha256:bbb", "nginx@sha256:aaa"]},
+            {"Id": "sha256:local", "RepoDigests": null}
+        ]"#;
+
+        let digests = parse_image_digests(output.as_bytes()).unwrap();
+        assert!(digests["sha256:local"].is_empty());
+
+        let nginx = &digests["sha256:4f0c"];
+        assert_eq!(
+            image_digest("nginx:alpine", nginx).as_deref(),
+            Some("nginx@sha256:aaa")
+        );
+        assert_eq!(
+            image_digest("mirror.local:5000/nginx", nginx).as_deref(),
+            Some("mirror.local:5000/nginx@sha256:bbb")
+        );
+        assert_eq!(
+            image_digest("sha256:4f0c", nginx).as_deref(),
+            Some("mirror.local:5000/nginx@sha256:bbb")
+        );
+        assert_eq!(image_digest("app", &digests["sha256:local"]), None);
+    }
+}
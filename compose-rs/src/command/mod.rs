@@ -1,8 +1,11 @@
-use std::{io, process::Output};
+use std::{
+    ffi::OsStr,
+    process::{Command, Output},
+};
 
-use crate::ComposeError;
+use crate::{error::CommandFailure, ComposeError};
 mod up;
-pub use up::UpCommand;
+pub use up::{PullPolicy, UpCommand};
 mod down;
 pub use down::DownCommand;
 mod ps;
@@ -13,6 +16,83 @@ pub mod stats;
 pub use stats::StatsCommand;
 pub mod start;
 pub use start::StartCommand;
+pub mod events;
+pub use events::EventsCommand;
+pub mod config;
+pub use config::ConfigCommand;
+mod exec;
+pub use exec::{CancellationToken, OutputLine, OutputStream};
+pub(crate) use exec::{ExecOptions, OutputSink};
+pub mod inspect;
+pub use inspect::InspectCommand;
+
+/// Implement the methods that set how a command builder is run, on a builder with an
+/// `options: ExecOptions` field. Builders where `timeout` is a compose flag name the
+/// timeout method differently, builders that only stream get the timeout and
+/// cancellation.
+macro_rules! exec_options {
+    (@bounds $command:ty, $timeout:ident) => {
+        impl $command {
+            /// Kill the command if it has not finished after the timeout, returning
+            /// [crate::ComposeError::Timeout] with the output captured so far.
+            pub fn $timeout(mut self, timeout: std::time::Duration) -> Self {
+                self.options.timeout = Some(timeout);
+                self
+            }
+
+            /// Kill the command once the token is cancelled, returning
+            /// [crate::ComposeError::Cancelled].
+            pub fn cancellation(mut self, token: &$crate::CancellationToken) -> Self {
+                self.options.cancellation = Some(token.clone());
+                self
+            }
+
+            pub(crate) fn with_options(mut self, options: $crate::command::ExecOptions) -> Self {
+                self.options = options;
+                self
+            }
+        }
+    };
+    (stream $command:ty) => {
+        exec_options!(@bounds $command, timeout);
+    };
+    ($command:ty, $timeout:ident) => {
+        exec_options!(@bounds $command, $timeout);
+
+        impl $command {
+            /// Retry the command according to the policy, overriding the policy of the
+            /// [crate::Compose].
+            pub fn retry(mut self, policy: $crate::retry::RetryPolicy) -> Self {
+                self.options.retry = Some(policy);
+                self
+            }
+
+            /// Call a function with each line the command prints, as it is printed.
+            ///
+            /// The output is still captured, so the command returns its result as usual.
+            pub fn on_output<F>(mut self, callback: F) -> Self
+            where
+                F: Fn(&$crate::command::OutputLine) + Send + Sync + 'static,
+            {
+                self.options.output = Some($crate::command::OutputSink::callback(callback));
+                self
+            }
+
+            /// Send each line the command prints to a channel, as it is printed.
+            pub fn output_channel(
+                mut self,
+                sender: std::sync::mpsc::Sender<$crate::command::OutputLine>,
+            ) -> Self {
+                self.options.output = Some($crate::command::OutputSink::channel(sender));
+                self
+            }
+        }
+    };
+    ($command:ty) => {
+        exec_options!($command, timeout);
+    };
+}
+use exec_options;
 
 pub trait ComposeCommand<ReturnT, ArgType = ()>
 where
@@ -34,20 +114,33 @@ impl ComposeCommandArgs for () {
 }
 
 pub(super) trait CatchOutput {
-    fn catch_output(self) -> Result<Output, ComposeError>;
+    fn catch_output_with(self, options: &ExecOptions) -> Result<Output, ComposeError>;
 }
 
-impl CatchOutput for io::Result<Output> {
-    fn catch_output(self) -> Result<Output, ComposeError> {
-        match self {
-            Ok(output) => {
-                if output.status.success() {
-                    Ok(output)
-                } else {
-                    Err(ComposeError::CommandFailed(output))
-                }
+/// Run the command to completion, classifying its stderr if it fails.
+impl CatchOutput for &mut Command {
+    fn catch_output_with(self, options: &ExecOptions) -> Result<Output, ComposeError> {
+        let mut run = || {
+            let output = exec::run(self, options)?;
+            if output.status.success() {
+                Ok(output)
+            } else {
+                Err(CommandFailure::new(self, &output).classify())
             }
-            Err(err) => Err(ComposeError::IoError(err)),
+        };
+
+        match &options.retry {
+            Some(policy) => policy.run(options.cancellation.as_ref(), run),
+            None => run(),
         }
     }
 }
+
+/// Run a `docker` command outside of compose, like `docker inspect`.
+pub(crate) fn docker<I, S>(args: I, options: &ExecOptions) -> Result<Output, ComposeError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    Command::new("docker").args(args).catch_output_with(options)
+}
@@ -1,14 +1,20 @@
 use crate::{container::Container, parser, ComposeCommand, ComposeError};
 
-use super::CatchOutput;
+use super::{exec_options, CatchOutput, ExecOptions};
 
 pub struct PsCommand {
     command: std::process::Command,
+    options: ExecOptions,
 }
 
+exec_options!(PsCommand);
+
 impl PsCommand {
     pub fn new(command: std::process::Command) -> Self {
-        Self { command }
+        Self {
+            command,
+            options: ExecOptions::default(),
+        }
     }
 }
 
@@ -17,9 +23,13 @@ impl ComposeCommand<Vec<Container>> for PsCommand {
 
     fn exec(self) -> Result<Vec<Container>, ComposeError> {
         let mut command = self.command;
-        command.arg(Self::COMMAND).arg("-a").arg("--no-trunc");
+        command
+            .arg(Self::COMMAND)
+            .arg("-a")
+            .arg("--no-trunc")
+            .args(["--format", "json"]);
 
-        let output = command.output().catch_output()?;
+        let output = command.catch_output_with(&self.options)?;
         let output = String::from_utf8_lossy(&output.stdout);
 
         parser::parse_ps(&output)
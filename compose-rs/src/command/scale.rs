@@ -1,15 +1,18 @@
-use super::{CatchOutput, ComposeCommandArgs};
+use super::{exec_options, CatchOutput, ComposeCommandArgs, ExecOptions};
 use crate::{ComposeCommand, ComposeError};
 
 pub enum ScaleArgs {
     NoDeps,
     Service(u32, String),
+    /// Print the actions without executing them
+    DryRun,
 }
 
 impl ComposeCommandArgs for ScaleArgs {
     fn args(&self) -> Vec<String> {
         match self {
             ScaleArgs::NoDeps => vec!["--no-deps".to_string()],
+            ScaleArgs::DryRun => vec!["--dry-run".to_string()],
             ScaleArgs::Service(count, service) => {
                 vec![format!("{}={}", service, count)]
             }
@@ -18,13 +21,17 @@ impl ComposeCommandArgs for ScaleArgs {
 }
 pub struct ScaleCommand {
     command: std::process::Command,
+    options: ExecOptions,
     args: Vec<ScaleArgs>,
 }
 
+exec_options!(ScaleCommand);
+
 impl ScaleCommand {
     pub fn new(command: std::process::Command) -> Self {
         Self {
             command,
+            options: ExecOptions::default(),
             args: Vec::new(),
         }
     }
@@ -34,6 +41,11 @@ impl ScaleCommand {
         self
     }
 
+    pub fn dry_run(mut self) -> Self {
+        self.args.push(ScaleArgs::DryRun);
+        self
+    }
+
     pub fn service(mut self, count: u32, service: &str) -> Self {
         self.args
             .push(ScaleArgs::Service(count, service.to_string()));
@@ -48,16 +60,20 @@ impl ComposeCommand<(), ScaleArgs> for ScaleCommand {
         let mut command = self.command;
         command.arg(Self::COMMAND);
 
-        // first use no_deps if it is present
-        if let Some(no_deps) = self.args.iter().find(|a| matches!(a, ScaleArgs::NoDeps)) {
-            command.args(&no_deps.args());
+        // first use the flags
+        for flag in self
+            .args
+            .iter()
+            .filter(|a| matches!(a, ScaleArgs::NoDeps | ScaleArgs::DryRun))
+        {
+            command.args(flag.args());
         }
 
         // then apply all service args
         let scale_args = self
             .args
             .iter()
-            .filter(|a| !matches!(a, ScaleArgs::NoDeps))
+            .filter(|a| matches!(a, ScaleArgs::Service(..)))
             .collect::<Vec<&ScaleArgs>>();
 
         if scale_args.is_empty() {
@@ -67,10 +83,10 @@ impl ComposeCommand<(), ScaleArgs> for ScaleCommand {
         }
 
         for arg in scale_args {
-            command.args(&arg.args());
+            command.args(arg.args());
         }
 
-        command.output().catch_output()?;
+        command.catch_output_with(&self.options)?;
 
         Ok(())
     }
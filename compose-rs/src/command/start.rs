@@ -1,6 +1,6 @@
 use crate::{ComposeCommand, ComposeError};
 
-use super::{CatchOutput, ComposeCommandArgs};
+use super::{exec_options, CatchOutput, ComposeCommandArgs, ExecOptions};
 
 pub enum StartArgs {
     /// Execute the command in dry-run mode
@@ -17,13 +17,17 @@ impl ComposeCommandArgs for StartArgs {
 
 pub struct StartCommand {
     command: std::process::Command,
+    options: ExecOptions,
     args: Vec<StartArgs>,
 }
 
+exec_options!(StartCommand);
+
 impl StartCommand {
     pub fn new(command: std::process::Command) -> Self {
         Self {
             command,
+            options: ExecOptions::default(),
             args: Vec::new(),
         }
     }
@@ -42,10 +46,10 @@ impl ComposeCommand<(), StartArgs> for StartCommand {
         command.arg(Self::COMMAND);
 
         for arg in self.args {
-            command.args(&arg.args());
+            command.args(arg.args());
         }
 
-        command.output().catch_output()?;
+        command.catch_output_with(&self.options)?;
 
         Ok(())
     }
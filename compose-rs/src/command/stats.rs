@@ -1,17 +1,14 @@
-use std::{
-    collections::HashMap,
-    io::{BufRead, BufReader},
-    sync::mpsc,
-    thread,
-    time::Duration,
-};
+use std::{collections::HashMap, sync::mpsc, thread, time::Duration};
 
 use parse_size::parse_size;
 use serde::{Deserialize, Serialize};
 
 use crate::{parser, ComposeCommand, ComposeError};
 
-use super::CatchOutput;
+use super::{exec, exec_options, CatchOutput, ExecOptions};
+
+mod record;
+pub use record::{StatsFrame, StatsRecorder, StatsReplay};
 
 //{"BlockIO":"0B / 0B","CPUPerc":"0.03%","Container":"9ca40acb565a","ID":"9ca40acb565a","MemPerc":"0.13%","MemUsage":"10MiB / 7.685GiB","Name":"examples-rqlite-1","NetIO":"1.39kB / 0B","PIDs":"10"}
 
@@ -27,6 +24,30 @@ pub struct StatsIO {
     output: u64,
 }
 
+impl StatsUsage {
+    /// Current usage in bytes.
+    pub fn usage(&self) -> u64 {
+        self.usage
+    }
+
+    /// Limit in bytes.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl StatsIO {
+    /// Bytes received or read.
+    pub fn input(&self) -> u64 {
+        self.input
+    }
+
+    /// Bytes sent or written.
+    pub fn output(&self) -> u64 {
+        self.output
+    }
+}
+
 // Implement the Deserialize trait for StatsIO, same as StatsUsage
 impl<'de> serde::Deserialize<'de> for StatsIO {
     fn deserialize<D>(deserializer: D) -> Result<StatsIO, D::Error>
@@ -53,9 +74,33 @@ impl<'de> serde::Deserialize<'de> for StatsIO {
                     parse_size(parts[1]).map_err(|_| E::custom("Failed to parse output"))?;
                 Ok(StatsIO { input, output })
             }
+
+            // Recorded stats are stored in their serialized form
+            fn visit_map<A>(self, mut map: A) -> Result<StatsIO, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut input = None;
+                let mut output = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "input" => input = Some(map.next_value()?),
+                        "output" => output = Some(map.next_value()?),
+                        _ => {
+                            map.next_value::<serde::de::IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                Ok(StatsIO {
+                    input: input.ok_or_else(|| serde::de::Error::missing_field("input"))?,
+                    output: output.ok_or_else(|| serde::de::Error::missing_field("output"))?,
+                })
+            }
         }
 
-        deserializer.deserialize_str(StatsIOVisitor)
+        deserializer.deserialize_any(StatsIOVisitor)
     }
 }
 
@@ -87,15 +132,45 @@ impl<'de> serde::Deserialize<'de> for StatsUsage {
                     limit: total,
                 })
             }
+
+            // Recorded stats are stored in their serialized form
+            fn visit_map<A>(self, mut map: A) -> Result<StatsUsage, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut usage = None;
+                let mut limit = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "usage" => usage = Some(map.next_value()?),
+                        "limit" => limit = Some(map.next_value()?),
+                        _ => {
+                            map.next_value::<serde::de::IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                Ok(StatsUsage {
+                    usage: usage.ok_or_else(|| serde::de::Error::missing_field("usage"))?,
+                    limit: limit.ok_or_else(|| serde::de::Error::missing_field("limit"))?,
+                })
+            }
         }
 
-        deserializer.deserialize_str(StatsUsageVisitor)
+        deserializer.deserialize_any(StatsUsageVisitor)
     }
 }
 
 #[derive(Serialize, Debug, Clone)]
 pub struct StatsPercentage(f64);
 
+impl StatsPercentage {
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for StatsPercentage {
     fn deserialize<D>(deserializer: D) -> Result<StatsPercentage, D::Error>
     where
@@ -118,9 +193,31 @@ impl<'de> serde::Deserialize<'de> for StatsPercentage {
                 let value = value.parse::<f64>().map_err(E::custom)?;
                 Ok(StatsPercentage(value))
             }
+
+            // Recorded stats are stored in their serialized form
+            fn visit_f64<E>(self, value: f64) -> Result<StatsPercentage, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(StatsPercentage(value))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<StatsPercentage, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(StatsPercentage(value as f64))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<StatsPercentage, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(StatsPercentage(value as f64))
+            }
         }
 
-        deserializer.deserialize_str(StatsPercentageVisitor)
+        deserializer.deserialize_any(StatsPercentageVisitor)
     }
 }
 
@@ -145,51 +242,52 @@ pub struct Stats {
 
 pub struct StatsCommand {
     command: std::process::Command,
+    options: ExecOptions,
     poll_interval: Option<Duration>,
 }
 
-type StatsIterator = Box<dyn Iterator<Item = Result<Vec<Stats>, ComposeError>> + Send>;
+exec_options!(StatsCommand);
+
+pub type StatsIterator = Box<dyn Iterator<Item = Result<Vec<Stats>, ComposeError>> + Send>;
 
 impl StatsCommand {
     pub fn new(cmd: std::process::Command) -> Self {
         Self {
             command: cmd,
+            options: ExecOptions::default(),
             poll_interval: None,
         }
     }
 
+    /// Stream the stats of the containers, averaged over the poll interval.
+    ///
+    /// The stream ends with [ComposeError::Timeout] or [ComposeError::Cancelled] once the
+    /// timeout expires or the token is cancelled. Retries and output callbacks only apply
+    /// to [ComposeCommand::exec].
     pub fn stream(self) -> Result<StatsIterator, ComposeError> {
         let mut command = self.command;
 
         command.arg("stats").arg("--format").arg("json");
 
-        let stdout = command
-            .stdout(std::process::Stdio::piped())
-            .spawn()?
-            .stdout
-            .ok_or(ComposeError::IoError(std::io::Error::other(
-                "Failed to open stdout",
-            )))?;
+        let mut lines = exec::stream(&mut command, &self.options)?;
 
         let (tx, rx) = mpsc::channel();
 
         thread::spawn(move || {
-            let mut reader = BufReader::new(stdout);
             let interval = self.poll_interval.unwrap_or(Duration::from_secs(1));
 
             loop {
-                let mut lines = Vec::new();
+                let mut batch = Vec::new();
                 let start_time = std::time::Instant::now();
 
                 while start_time.elapsed() < interval {
-                    let mut line = String::new();
-                    match reader.read_line(&mut line) {
-                        Ok(0) => break,
-                        Ok(_) => {
+                    match lines.next() {
+                        None => break,
+                        Some(Ok(line)) => {
                             let line = parser::remove_ansi_codes(&line);
 
                             if let Ok(line) = line {
-                                lines.push(line.trim().to_string());
+                                batch.push(line.trim().to_string());
                             } else {
                                 tx.send(Err(ComposeError::ParseError(
                                     "Failed to parse line".to_string(),
@@ -198,19 +296,18 @@ impl StatsCommand {
                                 return;
                             }
                         }
-                        Err(err) => {
-                            tx.send(Err(ComposeError::IoError(err)))
-                                .expect("Failed to send error");
+                        Some(Err(err)) => {
+                            tx.send(Err(err)).expect("Failed to send error");
                             return;
                         }
                     }
                 }
 
-                if lines.is_empty() {
+                if batch.is_empty() {
                     break;
                 }
 
-                let stats = lines
+                let stats = batch
                     .iter()
                     .map(|line| serde_json::from_str(line))
                     .collect::<Result<Vec<Stats>, _>>();
@@ -282,7 +379,7 @@ impl ComposeCommand<Vec<Stats>, ()> for StatsCommand {
             .arg("json")
             .arg("--no-stream");
 
-        let output = command.output().catch_output()?;
+        let output = command.catch_output_with(&self.options)?;
 
         let output = String::from_utf8_lossy(&output.stdout);
 
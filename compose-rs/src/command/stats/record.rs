@@ -0,0 +1,183 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ComposeError;
+
+use super::{Stats, StatsIterator};
+
+/// A single sample of the stats stream, as written to a recording.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StatsFrame {
+    /// Milliseconds since the unix epoch at which the frame was recorded.
+    pub timestamp: u64,
+    pub stats: Vec<Stats>,
+}
+
+/// Writes stats frames as newline-delimited JSON.
+pub struct StatsRecorder<W: Write> {
+    writer: W,
+}
+
+impl StatsRecorder<BufWriter<File>> {
+    /// Create a recorder writing to the file at `path`, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, ComposeError> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> StatsRecorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Record a frame, timestamped with the current time.
+    pub fn record(&mut self, stats: &[Stats]) -> Result<(), ComposeError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        self.write_frame(&StatsFrame {
+            timestamp,
+            stats: stats.to_vec(),
+        })
+    }
+
+    /// Write an already timestamped frame.
+    pub fn write_frame(&mut self, frame: &StatsFrame) -> Result<(), ComposeError> {
+        serde_json::to_writer(&mut self.writer, frame)?;
+        self.writer.write_all(b"\n")?;
+        // Flush every frame so a recording survives the process being killed
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write + Send + 'static> StatsRecorder<W> {
+    /// Record every frame of `stream` while passing it through unchanged.
+    ///
+    /// A failure to write the recording is yielded as an error item.
+    pub fn tee(mut self, stream: StatsIterator) -> StatsIterator {
+        Box::new(stream.map(move |item| {
+            if let Ok(stats) = &item {
+                self.record(stats)?;
+            }
+
+            item
+        }))
+    }
+}
+
+/// Replays a recording made with [StatsRecorder] through a [StatsIterator].
+pub struct StatsReplay<R: BufRead> {
+    reader: R,
+    speed: Option<f64>,
+}
+
+impl StatsReplay<BufReader<File>> {
+    /// Open the recording at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ComposeError> {
+        let file = File::open(path)?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead + Send + 'static> StatsReplay<R> {
+    /// Create a replay that plays back in real time.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            speed: Some(1.0),
+        }
+    }
+
+    /// Set the playback speed, e.g. `10.0` replays ten times faster than recorded.
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Yield frames as fast as they can be read, ignoring their timestamps.
+    pub fn unpaced(mut self) -> Self {
+        self.speed = None;
+        self
+    }
+
+    pub fn stream(self) -> Result<StatsIterator, ComposeError> {
+        if let Some(speed) = self.speed {
+            if !(speed.is_finite() && speed > 0.0) {
+                return Err(ComposeError::InvalidArguments(format!(
+                    "Invalid replay speed: {speed}"
+                )));
+            }
+        }
+
+        let speed = self.speed;
+        let mut lines = self.reader.lines();
+        let mut previous: Option<u64> = None;
+
+        let iter = std::iter::from_fn(move || loop {
+            let line = match lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(ComposeError::IoError(err))),
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let frame = match serde_json::from_str::<StatsFrame>(&line) {
+                Ok(frame) => frame,
+                Err(err) => return Some(Err(ComposeError::JsonError(err))),
+            };
+
+            if let (Some(speed), Some(previous)) = (speed, previous) {
+                let delta = frame.timestamp.saturating_sub(previous);
+                thread::sleep(Duration::from_millis(delta).div_f64(speed));
+            }
+            previous = Some(frame.timestamp);
+
+            return Some(Ok(frame.stats));
+        });
+
+        Ok(Box::new(iter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = r#"{"BlockIO":"0B / 0B","CPUPerc":"0.03%","Container":"9ca40acb565a","ID":"9ca40acb565a","MemPerc":"0.13%","MemUsage":"10MiB / 7.685GiB","Name":"examples-rqlite-1","NetIO":"1.39kB / 0B","PIDs":"10"}"#;
+
+    #[test]
+    fn test_record_and_replay() {
+        let stats: Stats = serde_json::from_str(LINE).unwrap();
+
+        let mut recording = Vec::new();
+        let mut recorder = StatsRecorder::new(&mut recording);
+        recorder.record(std::slice::from_ref(&stats)).unwrap();
+        recorder.record(&[stats.clone(), stats]).unwrap();
+
+        let frames = StatsReplay::new(std::io::Cursor::new(recording))
+            .unpaced()
+            .stream()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].len(), 2);
+        assert_eq!(frames[0][0].name, "examples-rqlite-1");
+        assert_eq!(frames[0][0].mem_usage.usage, 10 * 1024 * 1024);
+    }
+}
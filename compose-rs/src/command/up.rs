@@ -1,12 +1,30 @@
 use crate::{ComposeCommand, ComposeError};
 
-use super::{CatchOutput, ComposeCommandArgs};
+use super::{exec_options, CatchOutput, ComposeCommandArgs, ExecOptions};
+
+pub enum PullPolicy {
+    Always,
+    Missing,
+    Never,
+}
 
 pub enum UpArgs {
     /// Scale a service to a number of containers
     Scale(String, u32),
     /// Waits for containers to be running|healthy before returning
     Wait,
+    /// Pull images before starting containers
+    Pull(PullPolicy),
+    /// Recreate containers even if their configuration and image haven't changed
+    ForceRecreate,
+    /// Don't recreate containers that already exist
+    NoRecreate,
+    /// Don't start linked services
+    NoDeps,
+    /// Remove containers of services not defined in the compose files
+    RemoveOrphans,
+    /// Print the actions without executing them
+    DryRun,
 }
 
 impl ComposeCommandArgs for UpArgs {
@@ -16,20 +34,36 @@ impl ComposeCommandArgs for UpArgs {
                 vec!["--scale".to_string(), format!("{}={}", service, count)]
             }
             UpArgs::Wait => vec!["--wait".to_string()],
+            UpArgs::Pull(policy) => match policy {
+                PullPolicy::Always => vec!["--pull".to_string(), "always".to_string()],
+                PullPolicy::Missing => vec!["--pull".to_string(), "missing".to_string()],
+                PullPolicy::Never => vec!["--pull".to_string(), "never".to_string()],
+            },
+            UpArgs::ForceRecreate => vec!["--force-recreate".to_string()],
+            UpArgs::NoRecreate => vec!["--no-recreate".to_string()],
+            UpArgs::NoDeps => vec!["--no-deps".to_string()],
+            UpArgs::RemoveOrphans => vec!["--remove-orphans".to_string()],
+            UpArgs::DryRun => vec!["--dry-run".to_string()],
         }
     }
 }
 
 pub struct UpCommand {
     command: std::process::Command,
+    options: ExecOptions,
     args: Vec<UpArgs>,
+    services: Vec<String>,
 }
 
+exec_options!(UpCommand);
+
 impl UpCommand {
     pub fn new(command: std::process::Command) -> Self {
         Self {
             command,
+            options: ExecOptions::default(),
             args: Vec::new(),
+            services: Vec::new(),
         }
     }
 
@@ -42,6 +76,42 @@ impl UpCommand {
         self.args.push(UpArgs::Wait);
         self
     }
+
+    pub fn pull(mut self, policy: PullPolicy) -> Self {
+        self.args.push(UpArgs::Pull(policy));
+        self
+    }
+
+    pub fn force_recreate(mut self) -> Self {
+        self.args.push(UpArgs::ForceRecreate);
+        self
+    }
+
+    pub fn no_recreate(mut self) -> Self {
+        self.args.push(UpArgs::NoRecreate);
+        self
+    }
+
+    pub fn no_deps(mut self) -> Self {
+        self.args.push(UpArgs::NoDeps);
+        self
+    }
+
+    pub fn remove_orphans(mut self) -> Self {
+        self.args.push(UpArgs::RemoveOrphans);
+        self
+    }
+
+    pub fn dry_run(mut self) -> Self {
+        self.args.push(UpArgs::DryRun);
+        self
+    }
+
+    /// Only start the given service and its dependencies, can be called multiple times.
+    pub fn service(mut self, service: &str) -> Self {
+        self.services.push(service.to_string());
+        self
+    }
 }
 
 impl ComposeCommand<(), UpArgs> for UpCommand {
@@ -52,10 +122,12 @@ impl ComposeCommand<(), UpArgs> for UpCommand {
         command.arg(Self::COMMAND).arg("-d");
 
         for arg in self.args {
-            command.args(&arg.args());
+            command.args(arg.args());
         }
 
-        command.output().catch_output()?;
+        command.args(&self.services);
+
+        command.catch_output_with(&self.options)?;
 
         Ok(())
     }
@@ -1,10 +1,17 @@
+use std::{
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
 use serde::Serialize;
 
-use crate::ComposeError;
+use crate::{parser, ComposeError};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct PortMapping {
-    pub host_ip: Option<String>,
+    pub host_ip: Option<IpAddr>,
     pub host_port: Option<u16>,
     pub container_port: u16,
     pub protocol: String,
@@ -12,7 +19,7 @@ pub struct PortMapping {
 
 impl PortMapping {
     fn new(
-        host_ip: Option<String>,
+        host_ip: Option<IpAddr>,
         host_port: Option<u16>,
         container_port: u16,
         protocol: String,
@@ -25,97 +32,130 @@ impl PortMapping {
         }
     }
 
+    /// Parse a port as docker prints it, e.g. `4002/tcp`, `0.0.0.0:8080->80/tcp`,
+    /// `[::]:8080->80/tcp` or `:::8080->80/tcp`.
+    ///
+    /// Port ranges like `0.0.0.0:8000-8001->8000-8001/tcp` are expanded into one
+    /// mapping per port.
     pub(crate) fn from_string(port_string: &str) -> Result<Vec<Self>, ComposeError> {
-        // throw error
-        let (ports, protocol) = port_string.split_at(port_string.find('/').ok_or(
-            ComposeError::ParseError("Invalid port mapping: missing protocol".to_string()),
-        )?);
-        let protocol = protocol.trim_start_matches('/');
-
-        // Some ports are not mapped to the host
-        if !ports.contains("->") {
-            // Also catch these cases: 4001-4002/tcp
-            if ports.contains('-') {
-                let (start, end) = ports.split_at(ports.find('-').ok_or(
-                    ComposeError::ParseError("Invalid port mapping: missing '-'".to_string()),
-                )?);
-                let start = start.parse().map_err(|_| {
-                    ComposeError::ParseError(format!(
-                        "Invalid port mapping: invalid start port: {start}"
-                    ))
-                })?;
-                let end = end.trim_start_matches('-').parse().map_err(|_| {
-                    ComposeError::ParseError(format!(
-                        "Invalid port mapping: invalid end port: {end}"
-                    ))
-                })?;
-
-                return Ok((start..=end)
-                    .map(|port| Self::new(None, None, port, protocol.to_string()))
-                    .collect());
+        let error = |reason: &str| {
+            ComposeError::ParseError(format!("Invalid port mapping {port_string}: {reason}"))
+        };
+
+        let (ports, protocol) = port_string
+            .trim()
+            .rsplit_once('/')
+            .ok_or_else(|| error("missing protocol"))?;
+
+        let (host, container_ports) = match ports.rsplit_once("->") {
+            Some((host, container_ports)) => (Some(host), container_ports),
+            None => (None, ports),
+        };
+
+        let container_ports =
+            parse_port_range(container_ports).ok_or_else(|| error("invalid container port"))?;
+
+        let Some(host) = host else {
+            return Ok(container_ports
+                .map(|port| Self::new(None, None, port, protocol.to_string()))
+                .collect());
+        };
+
+        // The port follows the last colon, IPv6 addresses may be bracketed or not
+        let (host_ip, host_ports) = match host.rsplit_once(':') {
+            Some((ip, ports)) => {
+                let ip = ip.trim_start_matches('[').trim_end_matches(']');
+                let ip = match ip.is_empty() {
+                    true => None,
+                    false => Some(ip.parse::<IpAddr>().map_err(|_| error("invalid host IP"))?),
+                };
+                (ip, ports)
             }
+            None => (None, host),
+        };
+
+        let host_ports = parse_port_range(host_ports).ok_or_else(|| error("invalid host port"))?;
+        if host_ports.len() != container_ports.len() {
+            return Err(error("host and container port ranges differ in size"));
+        }
+
+        Ok(host_ports
+            .zip(container_ports)
+            .map(|(host_port, container_port)| {
+                Self::new(
+                    host_ip,
+                    Some(host_port),
+                    container_port,
+                    protocol.to_string(),
+                )
+            })
+            .collect())
+    }
+}
 
-            let container_port = ports.parse().map_err(|_| {
-                ComposeError::ParseError("Invalid port mapping: invalid container port".to_string())
-            })?;
+/// `8080` or `8000-8001`
+fn parse_port_range(ports: &str) -> Option<std::ops::RangeInclusive<u16>> {
+    let (start, end) = ports.split_once('-').unwrap_or((ports, ports));
+    let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+    (start <= end).then_some(start..=end)
+}
 
-            return Ok(vec![Self::new(
-                None,
-                None,
-                container_port,
-                protocol.to_string(),
-            )]);
+impl fmt::Display for PortMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(host_port) = self.host_port {
+            match self.host_ip {
+                Some(IpAddr::V6(ip)) => write!(f, "[{ip}]:")?,
+                Some(IpAddr::V4(ip)) => write!(f, "{ip}:")?,
+                None => {}
+            }
+            write!(f, "{host_port}->")?;
         }
+        write!(f, "{}/{}", self.container_port, self.protocol)
+    }
+}
 
-        let (host, container_port) = ports.split_at(ports.find("->").ok_or(
-            ComposeError::ParseError("Invalid port mapping: missing '->'".to_string()),
-        )?);
-        let container_port = container_port
-            .trim_start_matches("->")
-            .parse()
-            .map_err(|_| {
-                ComposeError::ParseError("Invalid port mapping: invalid container port".to_string())
-            })?;
-
-        let (host_ip, host_port) = host.split_at(host.find(':').ok_or(
-            ComposeError::ParseError("Invalid port mapping: missing host port".to_string()),
-        )?);
-
-        let host_port = host_port.trim_start_matches(':').parse().map_err(|_| {
-            ComposeError::ParseError("Invalid port mapping: invalid host port".to_string())
-        })?;
-
-        Ok(vec![Self::new(
-            Some(host_ip.to_string()),
-            Some(host_port),
-            container_port,
-            protocol.to_string(),
-        )])
+impl FromStr for PortMapping {
+    type Err = ComposeError;
+
+    /// Parse a single port mapping, ranges of more than one port are rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mappings = Self::from_string(s)?;
+        match mappings.len() {
+            1 => Ok(mappings.remove(0)),
+            _ => Err(ComposeError::ParseError(format!(
+                "Invalid port mapping {s}: expected a single port"
+            ))),
+        }
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
 pub enum Status {
     Up,
     Down,
+    Created,
     Restarting,
     Paused,
     Removing,
     Exited,
     Dead,
+    /// A status docker reported that is not known, as it was reported
+    Unknown(String),
 }
 
 impl Status {
-    fn from_string(status: &str) -> Self {
+    pub(crate) fn from_string(status: &str) -> Self {
         match status.to_lowercase().as_str() {
-            "up" => Self::Up,
+            "up" | "running" => Self::Up,
             "down" => Self::Down,
+            "created" => Self::Created,
             "restarting" => Self::Restarting,
             "paused" => Self::Paused,
-            "removing" => Self::Removing,
+            // `Removal In Progress`
+            "removing" | "removal" => Self::Removing,
             "exited" => Self::Exited,
             "dead" => Self::Dead,
-            _ => panic!("Unknown status"),
+            _ => Self::Unknown(status.to_string()),
         }
     }
 }
@@ -123,12 +163,14 @@ impl Status {
 #[derive(Serialize, Debug)]
 pub struct ContainerStatus {
     pub status: Status,
-    pub since: String,
+    /// How long the container has been in its status, e.g. the uptime of a running
+    /// container or the time since it exited. Zero for containers that were never started.
+    pub since: Duration,
     pub exit_code: Option<u8>,
 }
 
 impl ContainerStatus {
-    fn new(status: Status, since: String, exit_code: Option<u8>) -> Self {
+    fn new(status: Status, since: Duration, exit_code: Option<u8>) -> Self {
         Self {
             status,
             since,
@@ -136,22 +178,31 @@ impl ContainerStatus {
         }
     }
 
+    /// Parse a status like `Up 2 minutes (healthy)` or `Exited (1) About an hour ago`.
     pub(crate) fn from_string(status: &str) -> Self {
-        if status.contains(" (") {
-            let parts: Vec<&str> = status.split(' ').collect();
+        let (state, rest) = status.split_once(' ').unwrap_or((status, ""));
+        let mut state = Status::from_string(state);
 
-            let status = Status::from_string(parts[0]);
-            let exit_code = parts[1].trim_matches(|c| c == '(' || c == ')').parse().ok();
-            let since = parts[2..].join(" ");
+        let mut exit_code = None;
+        let mut since = rest;
+        if let Some(rest) = rest.strip_prefix('(') {
+            if let Some((code, rest)) = rest.split_once(')') {
+                exit_code = code.parse().ok();
+                since = rest;
+            }
+        }
 
-            return Self::new(status, since, exit_code);
+        // `Up 3 hours (Paused)`
+        if let Some((rest, note)) = since.split_once(" (") {
+            if note.trim_end_matches(')') == "Paused" {
+                state = Status::Paused;
+            }
+            since = rest;
         }
 
-        let parts: Vec<&str> = status.split(' ').collect();
-        let status = Status::from_string(parts[0]);
-        let since = parts[1..].join(" ");
+        let since = parser::parse_relative_duration(since).unwrap_or_default();
 
-        Self::new(status, since, None)
+        Self::new(state, since, exit_code)
     }
 }
 
@@ -161,7 +212,79 @@ pub struct Container {
     pub image: String,
     pub command: String,
     pub service: String,
-    pub created: String,
+    pub created: SystemTime,
     pub status: ContainerStatus,
     pub ports: Vec<PortMapping>,
+    /// Problems found while parsing the output of `ps`, e.g. ports that could not be parsed
+    pub warnings: Vec<String>,
+}
+
+impl Container {
+    /// How long the container has been running, `None` if it is not running.
+    pub fn uptime(&self) -> Option<Duration> {
+        (self.status.status == Status::Up).then_some(self.status.since)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_status() {
+        let status = ContainerStatus::from_string("Up 2 minutes (healthy)");
+        assert_eq!(status.status, Status::Up);
+        assert_eq!(status.since, Duration::from_secs(120));
+        assert_eq!(status.exit_code, None);
+
+        let status = ContainerStatus::from_string("Exited (137) About an hour ago");
+        assert_eq!(status.status, Status::Exited);
+        assert_eq!(status.since, Duration::from_secs(3600));
+        assert_eq!(status.exit_code, Some(137));
+
+        let status = ContainerStatus::from_string("Up 3 days (Paused)");
+        assert_eq!(status.status, Status::Paused);
+        assert_eq!(status.since, Duration::from_secs(3 * 86400));
+
+        let status = ContainerStatus::from_string("Created");
+        assert_eq!(status.status, Status::Created);
+        assert_eq!(status.since, Duration::ZERO);
+
+        // Statuses docker may add later, or leave empty, don't fail parsing
+        let status = ContainerStatus::from_string("Hibernating 2 minutes");
+        assert_eq!(status.status, Status::Unknown("Hibernating".to_string()));
+        assert_eq!(
+            ContainerStatus::from_string("").status,
+            Status::Unknown(String::new())
+        );
+    }
+
+    #[test]
+    fn test_port_mapping() {
+        let parse = |s: &str| PortMapping::from_string(s).unwrap();
+
+        let mapping = &parse("[::]:8080->80/tcp")[0];
+        assert_eq!(mapping.host_ip, Some("::".parse().unwrap()));
+        assert_eq!(mapping.host_port, Some(8080));
+        assert_eq!(mapping.container_port, 80);
+
+        assert_eq!(parse(":::8080->80/tcp"), parse("[::]:8080->80/tcp"));
+
+        let range = parse("0.0.0.0:8000-8001->9000-9001/udp");
+        assert_eq!(range.len(), 2);
+        assert_eq!(range[1].host_port, Some(8001));
+        assert_eq!(range[1].container_port, 9001);
+        assert_eq!(range[1].protocol, "udp");
+
+        assert_eq!(parse("4001-4003/tcp").len(), 3);
+
+        for port in ["0.0.0.0:8080->80/tcp", "[fe80::1]:53->53/udp", "4002/tcp"] {
+            assert_eq!(port.parse::<PortMapping>().unwrap().to_string(), port);
+        }
+
+        assert!(PortMapping::from_string("0.0.0.0:8000-8001->80/tcp").is_err());
+        assert!(PortMapping::from_string("localhost:80->80/tcp").is_err());
+        assert!(PortMapping::from_string("80").is_err());
+        assert!("4001-4002/tcp".parse::<PortMapping>().is_err());
+    }
 }
@@ -0,0 +1,360 @@
+//! Drift detection between the compose files and the containers of the project.
+//!
+//! The declared state comes from the resolved configuration and its config hashes,
+//! the observed state from inspecting the containers labelled with the project name.
+
+use std::{fmt, time::Instant};
+
+use crate::{
+    command::{docker, inspect::inspect_containers, ExecOptions},
+    Compose, ComposeCommand, ComposeError,
+};
+
+const PROJECT_LABEL: &str = "com.docker.compose.project";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Drift {
+    /// A declared service with at least one replica has no containers
+    MissingService { service: String },
+    /// A container belongs to a service that is not declared
+    OrphanContainer { service: String, container: String },
+    /// The number of running containers differs from the declared replicas
+    ReplicaCount {
+        service: String,
+        expected: u32,
+        actual: u32,
+    },
+    /// The container runs a different image than the declared reference resolves to
+    ImageChanged {
+        service: String,
+        container: String,
+        expected: String,
+        actual: String,
+    },
+    /// The configuration of the service changed since the container was created
+    ConfigChanged {
+        service: String,
+        container: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Drift {
+    pub fn service(&self) -> &str {
+        match self {
+            Drift::MissingService { service }
+            | Drift::OrphanContainer { service, .. }
+            | Drift::ReplicaCount { service, .. }
+            | Drift::ImageChanged { service, .. }
+            | Drift::ConfigChanged { service, .. } => service,
+        }
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::MissingService { service } => write!(f, "{service}: no containers"),
+            Drift::OrphanContainer { service, container } => {
+                write!(f, "{service}: orphan container {container}")
+            }
+            Drift::ReplicaCount {
+                service,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{service}: {actual} running replicas, expected {expected}"
+            ),
+            Drift::ImageChanged {
+                service,
+                container,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{service}: container {container} runs image {actual}, expected {expected}"
+            ),
+            Drift::ConfigChanged {
+                service,
+                container,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{service}: container {container} has config hash {actual}, expected {expected}"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DriftReport {
+    pub drifts: Vec<Drift>,
+}
+
+impl DriftReport {
+    /// Whether the containers match the compose files.
+    pub fn is_clean(&self) -> bool {
+        self.drifts.is_empty()
+    }
+
+    /// The drifts of a single service.
+    pub fn service<'a>(&'a self, service: &'a str) -> impl Iterator<Item = &'a Drift> + 'a {
+        self.drifts
+            .iter()
+            .filter(move |drift| drift.service() == service)
+    }
+
+    fn compare(declared: &[DeclaredService], observed: &[ObservedContainer]) -> Self {
+        let mut drifts = Vec::new();
+
+        for service in declared {
+            let containers = observed
+                .iter()
+                .filter(|container| container.service == service.name)
+                .collect::<Vec<_>>();
+
+            // Services scaled to zero are expected to have no containers
+            if containers.is_empty() && service.replicas > 0 {
+                drifts.push(Drift::MissingService {
+                    service: service.name.clone(),
+                });
+                continue;
+            }
+
+            let running = containers.iter().filter(|c| c.running).count() as u32;
+            if running != service.replicas {
+                drifts.push(Drift::ReplicaCount {
+                    service: service.name.clone(),
+                    expected: service.replicas,
+                    actual: running,
+                });
+            }
+
+            for container in containers {
+                if let (Some(expected), Some(actual)) = (&service.image_id, &container.image_id) {
+                    if expected != actual {
+                        drifts.push(Drift::ImageChanged {
+                            service: service.name.clone(),
+                            container: container.name.clone(),
+                            expected: expected.clone(),
+                            actual: actual.clone(),
+                        });
+                    }
+                }
+
+                if let (Some(expected), Some(actual)) =
+                    (&service.config_hash, &container.config_hash)
+                {
+                    if expected != actual {
+                        drifts.push(Drift::ConfigChanged {
+                            service: service.name.clone(),
+                            container: container.name.clone(),
+                            expected: expected.clone(),
+                            actual: actual.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for container in observed {
+            if !declared.iter().any(|s| s.name == container.service) {
+                drifts.push(Drift::OrphanContainer {
+                    service: container.service.clone(),
+                    container: container.name.clone(),
+                });
+            }
+        }
+
+        Self { drifts }
+    }
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for drift in &self.drifts {
+            writeln!(f, "{drift}")?;
+        }
+        Ok(())
+    }
+}
+
+struct DeclaredService {
+    name: String,
+    replicas: u32,
+    config_hash: Option<String>,
+    image_id: Option<String>,
+}
+
+pub(crate) struct ObservedContainer {
+    pub(crate) name: String,
+    pub(crate) service: String,
+    pub(crate) running: bool,
+    pub(crate) config_hash: Option<String>,
+    pub(crate) image_id: Option<String>,
+}
+
+/// The containers of the project, one-off containers of `run` are skipped.
+pub(crate) fn project_containers(
+    project: &str,
+    options: &ExecOptions,
+) -> Result<Vec<ObservedContainer>, ComposeError> {
+    let filter = format!("label={PROJECT_LABEL}={project}");
+    let output = docker(
+        ["ps", "-a", "-q", "--no-trunc", "--filter", &filter],
+        options,
+    )?;
+    let ids = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    Ok(inspect_containers(&ids, options)?
+        .into_iter()
+        .filter(|container| !container.is_oneoff())
+        .filter_map(|container| {
+            Some(ObservedContainer {
+                service: container.service()?.to_string(),
+                running: container.state.running,
+                config_hash: container.config_hash().map(str::to_string),
+                name: container.name,
+                image_id: Some(container.image_id),
+            })
+        })
+        .collect())
+}
+
+/// The ID of the local image a reference resolves to, if it exists.
+pub(crate) fn image_id(reference: &str, options: &ExecOptions) -> Option<String> {
+    let output = docker(
+        ["image", "inspect", "--format", "{{.Id}}", reference],
+        options,
+    )
+    .ok()?;
+
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!id.is_empty()).then_some(id)
+}
+
+impl Compose {
+    /// Compare the resolved configuration with the containers of the project.
+    ///
+    /// Images are compared by the ID of the local image the declared reference resolves
+    /// to, so a pulled tag that has not been deployed yet is reported as drift.
+    pub fn drift(&self) -> Result<DriftReport, ComposeError> {
+        let options = self.exec_options().step(Instant::now());
+        let config = self.config().exec()?;
+        let hashes = self.config().hashes(&[])?;
+
+        let project = match (self.project_name(), config.name.as_deref()) {
+            (Some(name), _) | (None, Some(name)) => name.to_string(),
+            (None, None) => {
+                return Err(ComposeError::ParseError(
+                    "Resolved configuration has no project name".to_string(),
+                ))
+            }
+        };
+
+        let declared = config
+            .services
+            .iter()
+            .map(|(name, service)| DeclaredService {
+                name: name.clone(),
+                replicas: service
+                    .scale
+                    .or_else(|| service.deploy.as_ref().and_then(|d| d.replicas))
+                    .unwrap_or(1),
+                config_hash: hashes.get(name).cloned(),
+                image_id: service
+                    .image
+                    .as_deref()
+                    .and_then(|image| image_id(image, &options)),
+            })
+            .collect::<Vec<_>>();
+
+        let observed = project_containers(&project, &options)?;
+
+        Ok(DriftReport::compare(&declared, &observed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declared(name: &str, replicas: u32) -> DeclaredService {
+        DeclaredService {
+            name: name.to_string(),
+            replicas,
+            config_hash: Some("hash".to_string()),
+            image_id: Some("sha256:new".to_string()),
+        }
+    }
+
+    fn observed(name: &str, service: &str, running: bool) -> ObservedContainer {
+        ObservedContainer {
+            name: name.to_string(),
+            service: service.to_string(),
+            running,
+            config_hash: Some("hash".to_string()),
+            image_id: Some("sha256:new".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let declared = [
+            declared("api", 2),
+            declared("db", 1),
+            declared("cache", 1),
+            declared("batch", 0),
+        ];
+
+        let mut edited = observed("app-db-1", "db", true);
+        edited.config_hash = Some("edited".to_string());
+        edited.image_id = Some("sha256:old".to_string());
+
+        let observed = [
+            observed("app-api-1", "api", true),
+            observed("app-api-2", "api", false),
+            edited,
+            observed("app-worker-1", "worker", true),
+        ];
+
+        let report = DriftReport::compare(&declared, &observed);
+        assert_eq!(
+            report.drifts,
+            [
+                Drift::ReplicaCount {
+                    service: "api".to_string(),
+                    expected: 2,
+                    actual: 1
+                },
+                Drift::ImageChanged {
+                    service: "db".to_string(),
+                    container: "app-db-1".to_string(),
+                    expected: "sha256:new".to_string(),
+                    actual: "sha256:old".to_string()
+                },
+                Drift::ConfigChanged {
+                    service: "db".to_string(),
+                    container: "app-db-1".to_string(),
+                    expected: "hash".to_string(),
+                    actual: "edited".to_string()
+                },
+                Drift::MissingService {
+                    service: "cache".to_string()
+                },
+                Drift::OrphanContainer {
+                    service: "worker".to_string(),
+                    container: "app-worker-1".to_string()
+                },
+            ]
+        );
+        assert!(!report.is_clean());
+        assert_eq!(report.service("db").count(), 2);
+    }
+}
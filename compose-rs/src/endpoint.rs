@@ -0,0 +1,177 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use serde::Serialize;
+
+use crate::{container::Container, Compose, ComposeCommand, ComposeError};
+
+/// Connectable host addresses of the published TCP ports of a compose stack,
+/// keyed by service and container port.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Endpoints {
+    services: BTreeMap<String, BTreeMap<u16, Vec<SocketAddr>>>,
+}
+
+impl Endpoints {
+    pub(crate) fn from_containers(containers: &[Container]) -> Self {
+        let mut endpoints = Self::default();
+
+        // Sort by replica number so replicas are listed in a stable order
+        let mut containers = containers.iter().collect::<Vec<_>>();
+        containers.sort_by_key(|container| replica_order(&container.name));
+
+        for container in containers {
+            let mut mappings = container
+                .ports
+                .iter()
+                .filter(|mapping| mapping.protocol == "tcp")
+                .filter_map(|mapping| {
+                    let host_port = mapping.host_port?;
+                    let host_ip = connectable_ip(mapping.host_ip);
+
+                    Some((mapping.container_port, SocketAddr::new(host_ip, host_port)))
+                })
+                .collect::<Vec<_>>();
+
+            // Prefer IPv4 addresses if a port is published on both stacks
+            mappings.sort_by_key(|(_, addr)| addr.is_ipv6());
+
+            for (container_port, addr) in mappings {
+                let addrs = endpoints
+                    .services
+                    .entry(container.service.clone())
+                    .or_default()
+                    .entry(container_port)
+                    .or_default();
+
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+        }
+
+        endpoints
+    }
+
+    /// All addresses of a container port of a service, one or more per replica.
+    pub fn get(&self, service: &str, port: u16) -> &[SocketAddr] {
+        self.services
+            .get(service)
+            .and_then(|ports| ports.get(&port))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The first address of a container port of a service.
+    pub fn first(&self, service: &str, port: u16) -> Option<SocketAddr> {
+        self.get(service, port).first().copied()
+    }
+
+    /// The published container ports of a service with their addresses.
+    pub fn service(&self, service: &str) -> Option<&BTreeMap<u16, Vec<SocketAddr>>> {
+        self.services.get(service)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16, &[SocketAddr])> {
+        self.services.iter().flat_map(|(service, ports)| {
+            ports
+                .iter()
+                .map(move |(port, addrs)| (service.as_str(), *port, addrs.as_slice()))
+        })
+    }
+}
+
+/// Order containers by the replica number compose appends to their name, e.g.
+/// `app-web-2` before `app-web-10`.
+fn replica_order(name: &str) -> (&str, Option<u32>) {
+    name.rsplit_once(['-', '_'])
+        .and_then(|(prefix, number)| Some((prefix, Some(number.parse().ok()?))))
+        .unwrap_or((name, None))
+}
+
+/// Translate the host IP docker reports into an address that can be connected to,
+/// wildcard addresses are replaced by the loopback address.
+fn connectable_ip(host_ip: Option<IpAddr>) -> IpAddr {
+    match host_ip {
+        None => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        Some(IpAddr::V4(ip)) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        Some(IpAddr::V6(ip)) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        Some(ip) => ip,
+    }
+}
+
+impl Compose {
+    /// Resolve the published host addresses of all services.
+    pub fn endpoints(&self) -> Result<Endpoints, ComposeError> {
+        let containers = self.ps().exec()?;
+        Ok(Endpoints::from_containers(&containers))
+    }
+
+    /// Resolve the host address a container port of a service is published on.
+    ///
+    /// If the service has multiple replicas the address of the first one is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ComposeError::InvalidArguments] if the port is not published.
+    pub fn port(&self, service: &str, port: u16) -> Result<SocketAddr, ComposeError> {
+        self.endpoints()?.first(service, port).ok_or_else(|| {
+            ComposeError::InvalidArguments(format!(
+                "Port {port} of service {service} is not published"
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::{ContainerStatus, PortMapping};
+
+    fn container(name: &str, ports: &str) -> Container {
+        Container {
+            name: name.to_string(),
+            image: "rqlite/rqlite:8.22.2".to_string(),
+            command: String::new(),
+            service: "rqlite".to_string(),
+            created: std::time::SystemTime::now(),
+            status: ContainerStatus::from_string("Up 2 minutes"),
+            ports: ports
+                .split(", ")
+                .flat_map(|p| PortMapping::from_string(p).unwrap())
+                .collect(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let endpoints = Endpoints::from_containers(&[
+            container("examples-rqlite-10", "0.0.0.0:32770->4001/tcp"),
+            container(
+                "examples-rqlite-2",
+                "0.0.0.0:32769->4001/tcp, 0.0.0.0:32800->4001/udp",
+            ),
+            container(
+                "examples-rqlite-1",
+                "0.0.0.0:32768->4001/tcp, 4002/tcp, 127.0.0.2:8080->80/tcp",
+            ),
+        ]);
+
+        assert_eq!(
+            endpoints.get("rqlite", 4001),
+            &[
+                "127.0.0.1:32768".parse::<SocketAddr>().unwrap(),
+                "127.0.0.1:32769".parse().unwrap(),
+                "127.0.0.1:32770".parse().unwrap()
+            ]
+        );
+        assert_eq!(
+            endpoints.first("rqlite", 80),
+            Some("127.0.0.2:8080".parse().unwrap())
+        );
+        assert!(endpoints.get("rqlite", 4002).is_empty());
+    }
+}
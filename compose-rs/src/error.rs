@@ -1,19 +1,307 @@
+use std::{
+    fmt,
+    process::{Command, Output},
+    time::Duration,
+};
+
 use thiserror::Error;
 
+use crate::{interpolation::InterpolationError, schema::SchemaViolation};
+
+/// A command that exited unsuccessfully.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandFailure {
+    /// The program and its arguments
+    pub argv: Vec<String>,
+    /// `None` if the process was killed by a signal
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandFailure {
+    pub(crate) fn new(command: &Command, output: &Output) -> Self {
+        Self {
+            argv: Self::argv(command),
+            exit_code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+
+    pub(crate) fn argv(command: &Command) -> Vec<String> {
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Classify the failure by the error message in its stderr.
+    pub(crate) fn classify(self) -> ComposeError {
+        let stderr = self.stderr.to_lowercase();
+
+        if stderr.contains("permission denied") {
+            return ComposeError::PermissionDenied(self);
+        }
+        if stderr.contains("cannot connect to the docker daemon")
+            || stderr.contains("is the docker daemon running")
+            || stderr.contains("error during connect")
+        {
+            return ComposeError::DaemonUnavailable(self);
+        }
+        if stderr.contains("pull access denied") {
+            let image = between(&self.stderr, "pull access denied for ", ",");
+            return ComposeError::PullAccessDenied {
+                image,
+                failure: self,
+            };
+        }
+        if stderr.contains("manifest unknown")
+            || stderr.contains("no such image")
+            || (stderr.contains("manifest for") && stderr.contains("not found"))
+        {
+            let image = between(&self.stderr, "manifest for ", " not found")
+                .or_else(|| between(&self.stderr, "No such image: ", "\n"));
+            return ComposeError::ImageNotFound {
+                image,
+                failure: self,
+            };
+        }
+        if stderr.contains("port is already allocated") || stderr.contains("address already in use")
+        {
+            let port = between(&self.stderr, "Bind for ", " failed")
+                // `listen tcp4 0.0.0.0:8080: bind: address already in use`
+                .or_else(|| between(&self.stderr, "listen tcp", ": bind:"))
+                .and_then(|address| address.rsplit(':').next()?.parse().ok());
+            return ComposeError::PortAllocated {
+                port,
+                failure: self,
+            };
+        }
+        if stderr.contains("no such service") {
+            let service = between(&self.stderr, "no such service: ", "\n");
+            return ComposeError::NoSuchService {
+                service,
+                failure: self,
+            };
+        }
+        if stderr.contains("is already in use by container") {
+            let name = between(&self.stderr, "container name \"", "\"")
+                .map(|name| name.trim_start_matches('/').to_string());
+            return ComposeError::NameConflict {
+                name,
+                failure: self,
+            };
+        }
+        if let Some(location) = ComposeFileLocation::parse(&self.stderr) {
+            return ComposeError::InvalidComposeFile {
+                location: Box::new(location),
+                failure: self,
+            };
+        }
+
+        ComposeError::CommandFailed(self)
+    }
+}
+
+impl fmt::Display for CommandFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` ", self.argv.join(" "))?;
+        match self.exit_code {
+            Some(code) => write!(f, "exited with code {code}")?,
+            None => write!(f, "was terminated by a signal")?,
+        }
+        write!(f, ": {}", self.stderr.trim())
+    }
+}
+
+/// Where compose found a compose file to be invalid, as far as its message tells.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ComposeFileLocation {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    /// The YAML path, e.g. `services.api.ports`
+    pub path: Option<String>,
+}
+
+impl ComposeFileLocation {
+    /// Parse messages like `yaml: line 3: mapping values are not allowed in this context`,
+    /// `validating /app/compose.yml: services.api additional properties 'foo' not allowed`
+    /// or `services.api.ports must be a list`.
+    fn parse(stderr: &str) -> Option<Self> {
+        let mut location = Self::default();
+
+        if let Some(rest) = stderr.split("validating ").nth(1) {
+            let (file, rest) = rest.split_once(": ")?;
+            location.file = Some(file.to_string());
+            location.path = rest.split_whitespace().next().map(str::to_string);
+            return Some(location);
+        }
+
+        if let Some(line) = between(stderr, "line ", ":") {
+            if stderr.contains("yaml:") {
+                location.line = line.parse().ok();
+                // `parsing /app/compose.yml: yaml: line 3: ...`
+                location.file = between(stderr, "parsing ", ": yaml:");
+                return Some(location);
+            }
+        }
+
+        let path = stderr
+            .split_whitespace()
+            .find(|word| word.starts_with("services.") || word.starts_with("networks."))?;
+        let is_invalid = ["must be", "is invalid", "not allowed", "does not match"]
+            .iter()
+            .any(|phrase| stderr.contains(phrase));
+        if !is_invalid {
+            return None;
+        }
+        location.path = Some(path.trim_end_matches([':', ',']).to_string());
+        Some(location)
+    }
+}
+
+impl fmt::Display for ComposeFileLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = [
+            self.file.clone(),
+            self.line.map(|line| format!("line {line}")),
+            self.path.clone(),
+        ];
+        let parts = parts.into_iter().flatten().collect::<Vec<_>>();
+        match parts.is_empty() {
+            true => write!(f, "unknown location"),
+            false => write!(f, "{}", parts.join(", ")),
+        }
+    }
+}
+
+/// The text between `start` and the next `end` (or the end of the line).
+fn between(text: &str, start: &str, end: &str) -> Option<String> {
+    let rest = &text[text.find(start)? + start.len()..];
+    let rest = rest.lines().next().unwrap_or(rest);
+    let value = rest.split(end).next().unwrap_or(rest).trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
 #[derive(Error, Debug)]
 pub enum ComposeError {
     #[error("Docker Compose file not found at {0}")]
     FileNotFound(String),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
-    #[error("Command failed: {} - {}", .0.status, String::from_utf8_lossy(&.0.stderr))]
-    CommandFailed(std::process::Output),
+    #[error("Command failed: {0}")]
+    CommandFailed(CommandFailure),
+    #[error("Docker daemon is not reachable: {0}")]
+    DaemonUnavailable(CommandFailure),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(CommandFailure),
+    #[error("Image {} not found: {failure}", .image.as_deref().unwrap_or("?"))]
+    ImageNotFound {
+        image: Option<String>,
+        failure: CommandFailure,
+    },
+    #[error("Pull access denied for image {}: {failure}", .image.as_deref().unwrap_or("?"))]
+    PullAccessDenied {
+        image: Option<String>,
+        failure: CommandFailure,
+    },
+    #[error("Port {} is already allocated: {failure}", .port.map(|p| p.to_string()).unwrap_or("?".to_string()))]
+    PortAllocated {
+        port: Option<u16>,
+        failure: CommandFailure,
+    },
+    #[error("No such service {}: {failure}", .service.as_deref().unwrap_or("?"))]
+    NoSuchService {
+        service: Option<String>,
+        failure: CommandFailure,
+    },
+    #[error("Invalid compose file at {location}: {failure}")]
+    InvalidComposeFile {
+        location: Box<ComposeFileLocation>,
+        failure: CommandFailure,
+    },
+    #[error("Container name {} is already in use: {failure}", .name.as_deref().unwrap_or("?"))]
+    NameConflict {
+        name: Option<String>,
+        failure: CommandFailure,
+    },
+    /// The command was killed after the timeout, the failure holds the output captured so far
+    #[error("Command timed out after {timeout:?}: {failure}")]
+    Timeout {
+        timeout: Duration,
+        failure: CommandFailure,
+    },
+    /// The command was killed by a cancellation token
+    #[error("Command cancelled: {0}")]
+    Cancelled(CommandFailure),
     #[error("Invalid Arguments: {0}")]
     InvalidArguments(String),
     #[error("Failed to Deserialize JSON: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("Failed to Deserialize YAML: {0}")]
+    YamlError(#[from] serde_yaml::Error),
     #[error("Failed to Parse docker output: {0}")]
     ParseError(String),
+    #[error("Service {0} did not become ready: {1}")]
+    NotReady(String, String),
+    #[error(transparent)]
+    BuilderError(#[from] ComposeBuilderError),
+    #[error("Interpolation failed: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    InterpolationError(Vec<InterpolationError>),
+    #[error("Schema validation failed: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    SchemaError(Vec<SchemaViolation>),
+    #[error("Dependency cycle between services: {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+}
+
+impl ComposeError {
+    /// Whether the error may go away when the command is run again, like a daemon that
+    /// is restarting or a registry that is briefly unavailable.
+    ///
+    /// [ComposeError::Timeout] is not transient, retrying it would multiply the time the
+    /// call is bounded by. A [crate::retry::RetryPolicy::retry_if] predicate can opt in.
+    pub fn is_transient(&self) -> bool {
+        const TRANSIENT: &[&str] = &[
+            "tls handshake timeout",
+            "i/o timeout",
+            "connection reset by peer",
+            "connection refused",
+            "temporary failure in name resolution",
+            "unexpected eof",
+            "toomanyrequests",
+            "502 bad gateway",
+            "503 service unavailable",
+            "504 gateway timeout",
+        ];
+
+        match self {
+            ComposeError::DaemonUnavailable(_) => true,
+            ComposeError::CommandFailed(failure) => {
+                let stderr = failure.stderr.to_lowercase();
+                TRANSIENT.iter().any(|message| stderr.contains(message))
+            }
+            _ => false,
+        }
+    }
+
+    /// The failed command, if the error was caused by one.
+    pub fn failure(&self) -> Option<&CommandFailure> {
+        match self {
+            ComposeError::CommandFailed(failure)
+            | ComposeError::DaemonUnavailable(failure)
+            | ComposeError::PermissionDenied(failure)
+            | ComposeError::ImageNotFound { failure, .. }
+            | ComposeError::PullAccessDenied { failure, .. }
+            | ComposeError::PortAllocated { failure, .. }
+            | ComposeError::NoSuchService { failure, .. }
+            | ComposeError::InvalidComposeFile { failure, .. }
+            | ComposeError::NameConflict { failure, .. }
+            | ComposeError::Timeout { failure, .. }
+            | ComposeError::Cancelled(failure) => Some(failure),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -24,4 +312,88 @@ pub enum ComposeBuilderError {
     MissingField(String),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Failed to Serialize YAML: {0}")]
+    YamlError(#[from] serde_yaml::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(stderr: &str) -> ComposeError {
+        CommandFailure {
+            argv: vec![
+                "docker".to_string(),
+                "compose".to_string(),
+                "up".to_string(),
+            ],
+            exit_code: Some(1),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        }
+        .classify()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert!(matches!(
+            classify("Cannot connect to the Docker daemon at unix:///var/run/docker.sock. Is the docker daemon running?"),
+            ComposeError::DaemonUnavailable(_)
+        ));
+        assert!(matches!(
+            classify("permission denied while trying to connect to the Docker daemon socket at unix:///var/run/docker.sock"),
+            ComposeError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            classify("Error response from daemon: pull access denied for acme/private, repository does not exist or may require 'docker login'"),
+            ComposeError::PullAccessDenied { image: Some(image), .. } if image == "acme/private"
+        ));
+        assert!(matches!(
+            classify("Error response from daemon: manifest for nginx:nope not found: manifest unknown: manifest unknown"),
+            ComposeError::ImageNotFound { image: Some(image), .. } if image == "nginx:nope"
+        ));
+        assert!(matches!(
+            classify("Error response from daemon: driver failed programming external connectivity on endpoint app-web-1: Bind for 0.0.0.0:8080 failed: port is already allocated"),
+            ComposeError::PortAllocated { port: Some(8080), .. }
+        ));
+        assert!(matches!(
+            classify("Error response from daemon: driver failed programming external connectivity on endpoint app-web-1: Error starting userland proxy: listen tcp4 0.0.0.0:8080: bind: address already in use"),
+            ComposeError::PortAllocated { port: Some(8080), .. }
+        ));
+        assert!(matches!(
+            classify("Error starting userland proxy: listen tcp6 [::]:9000: bind: address already in use"),
+            ComposeError::PortAllocated { port: Some(9000), .. }
+        ));
+        assert!(matches!(
+            classify("no such service: wbe"),
+            ComposeError::NoSuchService { service: Some(service), .. } if service == "wbe"
+        ));
+        assert!(matches!(
+            classify("Error response from daemon: Conflict. The container name \"/app-web-1\" is already in use by container \"9ca40acb565a\". You have to remove (or rename) that container to be able to reuse that name."),
+            ComposeError::NameConflict { name: Some(name), .. } if name == "app-web-1"
+        ));
+
+        let error = classify("parsing /app/compose.yml: yaml: line 3: mapping values are not allowed in this context");
+        let ComposeError::InvalidComposeFile { location, .. } = &error else {
+            panic!("unexpected error {error:?}");
+        };
+        assert_eq!(location.file.as_deref(), Some("/app/compose.yml"));
+        assert_eq!(location.line, Some(3));
+
+        let error = classify(
+            "validating /app/compose.yml: services.api additional properties 'foo' not allowed",
+        );
+        let ComposeError::InvalidComposeFile { location, .. } = &error else {
+            panic!("unexpected error {error:?}");
+        };
+        assert_eq!(location.path.as_deref(), Some("services.api"));
+
+        let error = classify("something else went wrong");
+        assert!(matches!(error, ComposeError::CommandFailed(_)));
+        assert_eq!(error.failure().unwrap().exit_code, Some(1));
+        assert_eq!(
+            error.to_string(),
+            "Command failed: `docker compose up` exited with code 1: something else went wrong"
+        );
+    }
 }
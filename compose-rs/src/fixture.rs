@@ -0,0 +1,321 @@
+use std::{
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    command::{OutputLine, OutputSink, OutputStream},
+    readiness::Readiness,
+    Compose, ComposeBuilder, ComposeCommand, ComposeError,
+};
+
+static FIXTURE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A compose stack that lives as long as this value, meant for integration tests.
+///
+/// The stack is started under a unique project name, so fixtures of the same file
+/// can run in parallel. On drop it is taken down with its volumes and orphans,
+/// also when the test panics.
+///
+/// Whether the stack is kept or taken down, and why tearing it down failed, is written
+/// to stderr, or to the callback set with [ComposeFixtureBuilder::on_output].
+///
+/// ```no_run
+/// use compose_rs::{fixture::ComposeFixture, ComposeError};
+///
+/// fn test() -> Result<(), ComposeError> {
+///     let fixture = ComposeFixture::start("docker-compose.yml")?;
+///     let addr = fixture.port("rqlite", 4001)?;
+///     // ...
+///     Ok(())
+/// }
+/// ```
+pub struct ComposeFixture {
+    compose: Compose,
+    keep: bool,
+    keep_on_failure: bool,
+    output: Option<OutputSink>,
+}
+
+impl ComposeFixture {
+    pub fn builder(path: impl ToString) -> ComposeFixtureBuilder {
+        ComposeFixtureBuilder::new(path)
+    }
+
+    /// Start the stack of the docker-compose file at `path` with the default options.
+    pub fn start(path: impl ToString) -> Result<Self, ComposeError> {
+        Self::builder(path).start()
+    }
+
+    /// The generated project name of the stack.
+    pub fn project_name(&self) -> &str {
+        // A fixture is always built with a project name
+        self.compose.project_name().unwrap_or_default()
+    }
+
+    pub fn compose(&self) -> &Compose {
+        &self.compose
+    }
+
+    /// Whether the stack is kept alive instead of being taken down on drop.
+    fn keeps_stack(&self, panicking: bool) -> bool {
+        self.keep || (self.keep_on_failure && panicking)
+    }
+
+    fn notice(&self, message: String) {
+        match &self.output {
+            Some(sink) => sink.send(&OutputLine {
+                stream: OutputStream::Stderr,
+                line: message,
+            }),
+            None => eprintln!("{message}"),
+        }
+    }
+}
+
+impl Deref for ComposeFixture {
+    type Target = Compose;
+
+    fn deref(&self) -> &Self::Target {
+        &self.compose
+    }
+}
+
+impl Drop for ComposeFixture {
+    fn drop(&mut self) {
+        if self.keeps_stack(thread::panicking()) {
+            self.notice(format!(
+                "Keeping compose project {} alive for inspection",
+                self.project_name()
+            ));
+            return;
+        }
+
+        self.notice(format!(
+            "Tearing down compose project {}",
+            self.project_name()
+        ));
+
+        let mut down = self.compose.down().remove_volumes().remove_orphans();
+        if let Some(sink) = self.output.clone() {
+            down = down.on_output(move |line| sink.send(line));
+        }
+        if let Err(e) = down.exec() {
+            self.notice(format!(
+                "Failed to tear down compose project {}: {e}",
+                self.project_name()
+            ));
+        }
+    }
+}
+
+pub struct ComposeFixtureBuilder {
+    path: String,
+    project_prefix: String,
+    services: Vec<String>,
+    readiness: Option<Readiness>,
+    keep_on_failure: bool,
+    output: Option<OutputSink>,
+}
+
+impl ComposeFixtureBuilder {
+    fn new(path: impl ToString) -> Self {
+        Self {
+            path: path.to_string(),
+            project_prefix: "compose-rs-test".to_string(),
+            services: Vec::new(),
+            readiness: None,
+            keep_on_failure: false,
+            output: None,
+        }
+    }
+
+    /// Prefix of the generated project name, defaults to `compose-rs-test`.
+    pub fn project_prefix(mut self, prefix: impl ToString) -> Self {
+        self.project_prefix = prefix.to_string();
+        self
+    }
+
+    /// Only start the given service and its dependencies, can be called multiple times.
+    pub fn service(mut self, service: &str) -> Self {
+        self.services.push(service.to_string());
+        self
+    }
+
+    /// Wait for these probes after `up`, instead of compose's own healthchecks.
+    pub fn readiness(mut self, readiness: Readiness) -> Self {
+        self.readiness = Some(readiness);
+        self
+    }
+
+    /// Keep the stack alive if the test panics, or if it fails to start.
+    pub fn keep_on_failure(mut self, keep: bool) -> Self {
+        self.keep_on_failure = keep;
+        self
+    }
+
+    /// Call a function with each line compose prints while the stack is started and
+    /// taken down, and with the notices of the fixture, instead of writing them to stderr.
+    pub fn on_output<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&OutputLine) + Send + Sync + 'static,
+    {
+        self.output = Some(OutputSink::callback(callback));
+        self
+    }
+
+    /// Start the stack and wait until it is ready.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [ComposeError] if the stack could not be started or did not become ready,
+    /// in which case it is taken down again unless [Self::keep_on_failure] is set.
+    pub fn start(self) -> Result<ComposeFixture, ComposeError> {
+        let compose = ComposeBuilder::new()
+            .path(&self.path)
+            .project_name(unique_project_name(&self.project_prefix))
+            .build()?;
+
+        let mut fixture = ComposeFixture {
+            compose,
+            keep: false,
+            keep_on_failure: self.keep_on_failure,
+            output: self.output,
+        };
+
+        let mut up = self
+            .services
+            .iter()
+            .fold(fixture.compose.up(), |up, service| up.service(service));
+        if let Some(sink) = fixture.output.clone() {
+            up = up.on_output(move |line| sink.send(line));
+        }
+
+        let result = match self.readiness {
+            Some(readiness) => up
+                .exec()
+                .and_then(|_| fixture.compose.wait_until(readiness)),
+            None => up.wait().exec(),
+        };
+
+        if let Err(e) = result {
+            fixture.keep = self.keep_on_failure;
+            return Err(e);
+        }
+
+        Ok(fixture)
+    }
+}
+
+fn unique_project_name(prefix: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let counter = FIXTURE_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    // Project names may only contain lowercase letters, digits, dashes and underscores
+    // and have to start with a letter or digit
+    let prefix = prefix.to_lowercase().replace(
+        |c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "-",
+    );
+    let prefix = match prefix.trim_start_matches(['-', '_']) {
+        "" => "test",
+        prefix => prefix,
+    };
+
+    format!("{prefix}-{}-{nanos:x}-{counter}", std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, sync::mpsc};
+
+    use super::*;
+
+    /// A fixture of an empty stack that records its notices.
+    fn fixture(keep: bool, keep_on_failure: bool) -> (ComposeFixture, mpsc::Receiver<OutputLine>) {
+        let compose = Compose::builder()
+            .yaml("services: {}\n")
+            .project_name(unique_project_name("test"))
+            .build()
+            .unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        let fixture = ComposeFixture {
+            compose,
+            keep,
+            keep_on_failure,
+            output: Some(OutputSink::channel(sender)),
+        };
+        (fixture, receiver)
+    }
+
+    fn torn_down(receiver: mpsc::Receiver<OutputLine>) -> bool {
+        let lines = receiver.try_iter().map(|l| l.line).collect::<Vec<_>>();
+        match lines.first() {
+            Some(line) if line.starts_with("Tearing down") => true,
+            Some(line) if line.starts_with("Keeping") => false,
+            _ => panic!("unexpected notices {lines:?}"),
+        }
+    }
+
+    #[test]
+    fn test_drop() {
+        // (keep, keep_on_failure) -> torn down after a passing test, after a panic
+        let cases = [
+            ((false, false), (true, true)),
+            ((false, true), (true, false)),
+            // A stack that failed to start with keep_on_failure
+            ((true, true), (false, false)),
+        ];
+
+        for ((keep, keep_on_failure), (passed, panicked)) in cases {
+            let (stack, receiver) = fixture(keep, keep_on_failure);
+            drop(stack);
+            assert_eq!(torn_down(receiver), passed);
+
+            let (stack, receiver) = fixture(keep, keep_on_failure);
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(move || {
+                let _stack = stack;
+                panic!("test failed");
+            }));
+            assert!(result.is_err());
+            assert_eq!(torn_down(receiver), panicked);
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let builder = ComposeFixture::builder("docker-compose.yml")
+            .project_prefix("My Tests")
+            .service("db")
+            .service("api")
+            .keep_on_failure(true);
+
+        assert_eq!(builder.path, "docker-compose.yml");
+        assert_eq!(builder.services, ["db", "api"]);
+        assert!(builder.keep_on_failure);
+        assert!(builder.readiness.is_none());
+
+        let name = unique_project_name(&builder.project_prefix);
+        assert!(name.starts_with("my-tests-"));
+        assert!(name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_'));
+        assert_ne!(name, unique_project_name(&builder.project_prefix));
+
+        for prefix in ["", "_x", "Ä", "--"] {
+            let name = unique_project_name(prefix);
+            assert!(
+                name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit()),
+                "{prefix:?} -> {name}"
+            );
+        }
+        assert!(unique_project_name("").starts_with("test-"));
+        assert!(unique_project_name("_x").starts_with("x-"));
+    }
+}
@@ -0,0 +1,370 @@
+//! The dependency graph of the services of a compose file.
+//!
+//! A service depends on the services in its `depends_on` and `links`, and on the
+//! service whose network namespace it joins with `network_mode: service:<name>`.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{model::ComposeFile, Compose, ComposeError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeKind {
+    /// `depends_on` with its condition, e.g. `service_healthy`
+    DependsOn(String),
+    Link,
+    NetworkMode,
+}
+
+/// `from` depends on `to`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    services: Vec<String>,
+    edges: Vec<Edge>,
+}
+
+impl DependencyGraph {
+    pub fn from_model(model: &ComposeFile) -> Self {
+        let mut edges = Vec::new();
+
+        for (name, service) in &model.services {
+            for (dependency, options) in &service.depends_on {
+                edges.push(Edge {
+                    from: name.clone(),
+                    to: dependency.clone(),
+                    kind: EdgeKind::DependsOn(options.condition.clone()),
+                });
+            }
+
+            for link in &service.links {
+                // `service[:alias]`
+                let target = link.split(':').next().unwrap_or(link);
+                edges.push(Edge {
+                    from: name.clone(),
+                    to: target.to_string(),
+                    kind: EdgeKind::Link,
+                });
+            }
+
+            if let Some(target) = service
+                .network_mode
+                .as_deref()
+                .and_then(|mode| mode.strip_prefix("service:"))
+            {
+                edges.push(Edge {
+                    from: name.clone(),
+                    to: target.to_string(),
+                    kind: EdgeKind::NetworkMode,
+                });
+            }
+        }
+
+        Self {
+            services: model.services.keys().cloned().collect(),
+            edges,
+        }
+    }
+
+    /// The services in the order they are declared.
+    pub fn services(&self) -> &[String] {
+        &self.services
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// The services `service` depends on directly.
+    pub fn dependencies(&self, service: &str) -> Vec<&str> {
+        let mut dependencies = Vec::new();
+        for edge in self.edges.iter().filter(|edge| edge.from == service) {
+            if !dependencies.contains(&edge.to.as_str()) {
+                dependencies.push(edge.to.as_str());
+            }
+        }
+        dependencies
+    }
+
+    /// The services that depend on `service` directly.
+    pub fn dependents(&self, service: &str) -> Vec<&str> {
+        let mut dependents = Vec::new();
+        for edge in self.edges.iter().filter(|edge| edge.to == service) {
+            if !dependents.contains(&edge.from.as_str()) {
+                dependents.push(edge.from.as_str());
+            }
+        }
+        dependents
+    }
+
+    /// All services `service` depends on, directly or indirectly, in start order.
+    pub fn transitive_dependencies(&self, service: &str) -> Vec<&str> {
+        fn visit<'a>(
+            graph: &'a DependencyGraph,
+            service: &str,
+            seen: &mut Vec<&'a str>,
+            ordered: &mut Vec<&'a str>,
+        ) {
+            for dependency in graph.dependencies(service) {
+                if !seen.contains(&dependency) {
+                    seen.push(dependency);
+                    visit(graph, dependency, seen, ordered);
+                    ordered.push(dependency);
+                }
+            }
+        }
+
+        let mut ordered = Vec::new();
+        visit(self, service, &mut Vec::new(), &mut ordered);
+
+        ordered.retain(|dependency| *dependency != service);
+        ordered
+    }
+
+    /// The cycles of the graph, each as the services involved in declaration order.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        Tarjan::new(self).run()
+    }
+
+    /// The order compose starts the services in, dependencies come first and otherwise
+    /// the declaration order is kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ComposeError::DependencyCycle] if the dependencies contain a cycle.
+    pub fn start_order(&self) -> Result<Vec<String>, ComposeError> {
+        if let Some(cycle) = self.cycles().into_iter().next() {
+            return Err(ComposeError::DependencyCycle(cycle));
+        }
+
+        let mut order: Vec<String> = Vec::with_capacity(self.services.len());
+        while order.len() < self.services.len() {
+            // The first service whose known dependencies have all been started
+            let next = self.services.iter().find(|service| {
+                !order.contains(service)
+                    && self
+                        .dependencies(service)
+                        .iter()
+                        .filter(|dependency| self.services.iter().any(|s| s == *dependency))
+                        .all(|dependency| order.iter().any(|s| s == dependency))
+            });
+
+            match next {
+                Some(service) => order.push(service.clone()),
+                None => break,
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Render the graph in the Graphviz DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph compose {\n    rankdir=LR;\n");
+
+        for service in &self.services {
+            let _ = writeln!(dot, "    \"{service}\";");
+        }
+        for edge in &self.edges {
+            let attributes = match &edge.kind {
+                EdgeKind::DependsOn(condition) => format!("label=\"{condition}\""),
+                EdgeKind::Link => "label=\"link\", style=dashed".to_string(),
+                EdgeKind::NetworkMode => "label=\"network_mode\", style=dotted".to_string(),
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [{attributes}];",
+                edge.from, edge.to
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the graph as a Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let mut ids = HashMap::new();
+        let mut mermaid = String::from("flowchart LR\n");
+
+        let mut id = |mermaid: &mut String, service: &str| -> String {
+            let next = ids.len();
+            ids.entry(service.to_string())
+                .or_insert_with(|| {
+                    // Service names may contain characters Mermaid does not allow in ids
+                    let _ = writeln!(mermaid, "    s{next}[\"{service}\"]");
+                    format!("s{next}")
+                })
+                .clone()
+        };
+
+        for service in &self.services {
+            id(&mut mermaid, service);
+        }
+        for edge in &self.edges {
+            let from = id(&mut mermaid, &edge.from);
+            let to = id(&mut mermaid, &edge.to);
+            let arrow = match &edge.kind {
+                EdgeKind::DependsOn(condition) => format!("-->|{condition}|"),
+                EdgeKind::Link => "-.->|link|".to_string(),
+                EdgeKind::NetworkMode => "-.->|network_mode|".to_string(),
+            };
+            let _ = writeln!(mermaid, "    {from} {arrow} {to}");
+        }
+
+        mermaid
+    }
+}
+
+/// Tarjan's algorithm for the strongly connected components of the graph.
+struct Tarjan<'a> {
+    graph: &'a DependencyGraph,
+    index: HashMap<&'a str, usize>,
+    low_link: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    cycles: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(graph: &'a DependencyGraph) -> Self {
+        Self {
+            graph,
+            index: HashMap::new(),
+            low_link: HashMap::new(),
+            stack: Vec::new(),
+            cycles: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> Vec<Vec<String>> {
+        for service in &self.graph.services {
+            if !self.index.contains_key(service.as_str()) {
+                self.connect(service);
+            }
+        }
+
+        self.cycles
+    }
+
+    fn connect(&mut self, service: &'a str) {
+        let index = self.index.len();
+        self.index.insert(service, index);
+        self.low_link.insert(service, index);
+        self.stack.push(service);
+
+        for dependency in self.graph.dependencies(service) {
+            if !self.index.contains_key(dependency) {
+                self.connect(dependency);
+                let low = self.low_link[service].min(self.low_link[dependency]);
+                self.low_link.insert(service, low);
+            } else if self.stack.contains(&dependency) {
+                let low = self.low_link[service].min(self.index[dependency]);
+                self.low_link.insert(service, low);
+            }
+        }
+
+        if self.low_link[service] == index {
+            let position = self.stack.iter().rposition(|s| *s == service).unwrap_or(0);
+            let component = self.stack.split_off(position);
+
+            let is_cycle =
+                component.len() > 1 || self.graph.dependencies(service).contains(&service);
+            if is_cycle {
+                let mut cycle = self
+                    .graph
+                    .services
+                    .iter()
+                    .filter(|s| component.contains(&s.as_str()))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if cycle.is_empty() {
+                    cycle = component.iter().map(|s| s.to_string()).collect();
+                }
+                self.cycles.push(cycle);
+            }
+        }
+    }
+}
+
+impl Compose {
+    /// Build the dependency graph of the services from the compose files.
+    pub fn graph(&self) -> Result<DependencyGraph, ComposeError> {
+        Ok(DependencyGraph::from_model(&self.model()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE_FILE: &str = r#"
+services:
+  web:
+    image: nginx
+    links: ["api:backend"]
+  api:
+    image: api
+    depends_on:
+      db:
+        condition: service_healthy
+      cache:
+        condition: service_started
+  sidecar:
+    image: envoy
+    network_mode: service:api
+  cache:
+    image: redis
+  db:
+    image: postgres
+"#;
+
+    #[test]
+    fn test_dependency_graph() {
+        let model: ComposeFile = COMPOSE_FILE.parse().unwrap();
+        let graph = DependencyGraph::from_model(&model);
+
+        assert!(graph.cycles().is_empty());
+        assert_eq!(
+            graph.start_order().unwrap(),
+            ["cache", "db", "api", "web", "sidecar"]
+        );
+        assert_eq!(graph.transitive_dependencies("web"), ["db", "cache", "api"]);
+        assert_eq!(graph.dependents("api"), ["web", "sidecar"]);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("\"api\" -> \"db\" [label=\"service_healthy\"];"));
+        assert!(dot.contains("\"web\" -> \"api\" [label=\"link\", style=dashed];"));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.starts_with("flowchart LR\n    s0[\"web\"]\n"));
+        assert!(mermaid.contains("s1 -->|service_healthy| s4"));
+    }
+
+    #[test]
+    fn test_dependency_cycle() {
+        let model: ComposeFile = "services:
+  a:
+    depends_on: [b]
+  b:
+    depends_on: [c]
+  c:
+    depends_on: [a]
+  d:
+    depends_on: [d]
+"
+        .parse()
+        .unwrap();
+        let graph = DependencyGraph::from_model(&model);
+
+        assert_eq!(graph.cycles(), [vec!["a", "b", "c"], vec!["d"]]);
+        assert!(matches!(
+            graph.start_order(),
+            Err(ComposeError::DependencyCycle(cycle)) if cycle == ["a", "b", "c"]
+        ));
+    }
+}
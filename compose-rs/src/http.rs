@@ -0,0 +1,56 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::ComposeError;
+
+/// Send a minimal HTTP/1.1 request and return the response status code.
+pub(crate) fn request(
+    addr: impl ToSocketAddrs,
+    host: &str,
+    method: &str,
+    path: &str,
+    body: Option<(&str, &str)>,
+    timeout: Duration,
+) -> Result<u16, ComposeError> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| ComposeError::InvalidArguments(format!("Could not resolve {host}")))?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n"
+    )?;
+    match body {
+        Some((content_type, body)) => write!(
+            stream,
+            "Content-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )?,
+        None => write!(stream, "\r\n")?,
+    }
+
+    // Only the status line is of interest
+    let mut response = Vec::new();
+    let mut buf = [0; 512];
+    while !response.contains(&b'\n') {
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..read]);
+    }
+
+    String::from_utf8_lossy(&response)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| ComposeError::ParseError("Invalid HTTP response".to_string()))
+}
@@ -0,0 +1,642 @@
+//! Variable interpolation and `.env` loading following compose's rules.
+//!
+//! Supported syntax is `$VAR`, `${VAR}`, `${VAR:-default}`, `${VAR-default}`,
+//! `${VAR:?error}`, `${VAR?error}`, `${VAR:+replacement}`, `${VAR+replacement}`
+//! and `$$` to escape a literal `$`. Defaults and replacements may be interpolated themselves.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde_yaml::Value;
+
+use crate::{merge, model::ComposeFile, schema::Positions, Compose, ComposeError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpolationErrorKind {
+    /// The variable is not set and has no default
+    Missing,
+    /// The variable is not set (or empty with `:?`), with the message given in the file
+    Required(String),
+    /// The expression or `.env` line could not be parsed
+    InvalidSyntax(String),
+}
+
+/// An unresolved variable or syntax error, with the position it occurred at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpolationError {
+    pub file: Option<PathBuf>,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number, counted in characters
+    pub column: usize,
+    pub variable: String,
+    pub kind: InterpolationErrorKind,
+}
+
+impl fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        write!(f, "{}:{}: ", self.line, self.column)?;
+
+        match &self.kind {
+            InterpolationErrorKind::Missing => {
+                write!(f, "variable {} is not set", self.variable)
+            }
+            InterpolationErrorKind::Required(message) if message.is_empty() => {
+                write!(f, "required variable {} is missing a value", self.variable)
+            }
+            InterpolationErrorKind::Required(message) => {
+                write!(
+                    f,
+                    "required variable {} is missing a value: {message}",
+                    self.variable
+                )
+            }
+            InterpolationErrorKind::InvalidSyntax(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+/// The variables available for interpolation.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    variables: HashMap<String, String>,
+}
+
+impl Environment {
+    /// An environment without any variables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The environment of the current process.
+    pub fn from_process() -> Self {
+        Self {
+            variables: std::env::vars().collect(),
+        }
+    }
+
+    /// Load the environment with compose's precedence, from lowest to highest:
+    /// the `env_files` in order, or `.env` in `project_dir` if no env files are given,
+    /// and then the environment of the current process.
+    pub fn load(project_dir: &Path, env_files: &[PathBuf]) -> Result<Self, ComposeError> {
+        let process = Self::from_process();
+        let mut environment = Self::new();
+
+        let default_file = project_dir.join(".env");
+        let files = match env_files.is_empty() {
+            true if default_file.exists() => vec![default_file],
+            true => Vec::new(),
+            false => env_files.to_vec(),
+        };
+
+        let mut errors = Vec::new();
+        for file in files {
+            if !file.exists() {
+                return Err(ComposeError::FileNotFound(
+                    file.to_string_lossy().to_string(),
+                ));
+            }
+
+            let content = fs::read_to_string(&file)?;
+            // Values in env files may reference variables of the process and earlier files
+            let mut lookup = environment.clone();
+            lookup.extend(&process);
+
+            if let Err(e) = environment.parse_env_file(&content, Some(&file), &lookup) {
+                errors.extend(e);
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ComposeError::InterpolationError(errors));
+        }
+
+        environment.extend(&process);
+        Ok(environment)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: impl ToString, value: impl ToString) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    /// Add all variables of `other`, overriding existing ones.
+    pub fn extend(&mut self, other: &Environment) {
+        for (name, value) in &other.variables {
+            self.variables.insert(name.clone(), value.clone());
+        }
+    }
+
+    /// Parse the content of an env file and add its variables.
+    ///
+    /// Unquoted and double quoted values are interpolated with `lookup` and the
+    /// variables of earlier lines.
+    pub fn parse_env_file(
+        &mut self,
+        content: &str,
+        file: Option<&Path>,
+        lookup: &Environment,
+    ) -> Result<(), Vec<InterpolationError>> {
+        let mut lookup = lookup.clone();
+        let mut errors = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let syntax_error = |message: &str| InterpolationError {
+                file: file.map(Path::to_path_buf),
+                line: index + 1,
+                column: 1,
+                variable: String::new(),
+                kind: InterpolationErrorKind::InvalidSyntax(message.to_string()),
+            };
+
+            let declaration = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+            let (name, raw) = match declaration.split_once('=') {
+                Some((name, raw)) => (name.trim(), raw.trim()),
+                // A bare name takes its value from the process
+                None => (declaration, ""),
+            };
+
+            if !is_valid_name(name) {
+                errors.push(syntax_error(&format!("invalid variable name: {name}")));
+                continue;
+            }
+
+            if !declaration.contains('=') {
+                if let Some(value) = lookup.get(name).map(str::to_string) {
+                    self.set(name, &value);
+                }
+                continue;
+            }
+
+            let value = if let Some(quoted) = raw.strip_prefix('\'') {
+                // Single quoted values are taken literally
+                match quoted.strip_suffix('\'') {
+                    Some(value) => value.to_string(),
+                    None => {
+                        errors.push(syntax_error("unterminated single quote"));
+                        continue;
+                    }
+                }
+            } else {
+                let (value, column) = match raw.strip_prefix('"') {
+                    Some(quoted) => match quoted.strip_suffix('"') {
+                        Some(value) => (unescape(value), line.find('"').unwrap_or(0) + 2),
+                        None => {
+                            errors.push(syntax_error("unterminated double quote"));
+                            continue;
+                        }
+                    },
+                    None => {
+                        // Inline comments need to be preceded by whitespace
+                        let value = match raw.find(" #") {
+                            Some(index) => raw[..index].trim_end(),
+                            None => raw,
+                        };
+                        (value.to_string(), line.find('=').unwrap_or(0) + 2)
+                    }
+                };
+
+                let mut context = Context {
+                    file,
+                    environment: &lookup,
+                    errors: &mut errors,
+                };
+                context.interpolate(&value, index + 1, column)
+            };
+
+            lookup.set(name, &value);
+            self.set(name, value);
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+/// Interpolate all variables in the string values of the YAML document `text`,
+/// collecting every unresolved variable.
+///
+/// Like compose, only values are interpolated: comments and keys are left alone and
+/// substituted text can't change the structure of the document. Unquoted values that
+/// become a number or boolean are read as such. Text that isn't valid YAML is
+/// returned unchanged, for the caller to report the syntax error.
+pub fn interpolate(
+    text: &str,
+    file: Option<&Path>,
+    environment: &Environment,
+) -> Result<String, Vec<InterpolationError>> {
+    let Ok(mut value) = serde_yaml::from_str::<Value>(text) else {
+        return Ok(text.to_string());
+    };
+
+    let positions = Positions::index(text);
+    let mut errors = Vec::new();
+    let mut context = Context {
+        file,
+        environment,
+        errors: &mut errors,
+    };
+    context.interpolate_value(&mut value, &mut Vec::new(), &positions);
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(serde_yaml::to_string(&value).unwrap_or_else(|_| text.to_string()))
+}
+
+struct Context<'a> {
+    file: Option<&'a Path>,
+    environment: &'a Environment,
+    errors: &'a mut Vec<InterpolationError>,
+}
+
+impl Context<'_> {
+    fn error(&mut self, line: usize, column: usize, variable: &str, kind: InterpolationErrorKind) {
+        self.errors.push(InterpolationError {
+            file: self.file.map(Path::to_path_buf),
+            line,
+            column,
+            variable: variable.to_string(),
+            kind,
+        });
+    }
+
+    /// Interpolate the string scalars of `value`, `path` is the path of `value` in the document.
+    fn interpolate_value(
+        &mut self,
+        value: &mut Value,
+        path: &mut Vec<String>,
+        positions: &Positions,
+    ) {
+        match value {
+            Value::String(text) if text.contains('$') => {
+                let (line, column) = positions.scalar(path);
+                let interpolated = self.interpolate(text, line, column);
+
+                *value = match serde_yaml::from_str::<Value>(&interpolated) {
+                    Ok(parsed @ (Value::Bool(_) | Value::Number(_)))
+                        if positions.is_plain(path) =>
+                    {
+                        parsed
+                    }
+                    _ => Value::String(interpolated),
+                };
+            }
+            Value::Sequence(items) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    self.interpolate_value(item, path, positions);
+                    path.pop();
+                }
+            }
+            Value::Mapping(mapping) => {
+                for (key, item) in mapping.iter_mut() {
+                    path.push(key_segment(key));
+                    self.interpolate_value(item, path, positions);
+                    path.pop();
+                }
+            }
+            Value::Tagged(tagged) => self.interpolate_value(&mut tagged.value, path, positions),
+            _ => {}
+        }
+    }
+
+    /// Interpolate a single line, `column` is the column `input` starts at.
+    fn interpolate(&mut self, input: &str, line: usize, column: usize) -> String {
+        let chars = input.chars().collect::<Vec<_>>();
+        let mut result = String::with_capacity(input.len());
+        let mut i = 0;
+
+        while i < chars.len() {
+            if chars[i] != '$' {
+                result.push(chars[i]);
+                i += 1;
+                continue;
+            }
+
+            let start = column + i;
+            match chars.get(i + 1) {
+                Some('$') => {
+                    result.push('$');
+                    i += 2;
+                }
+                Some('{') => {
+                    // Find the matching closing brace, defaults may contain nested expressions
+                    let mut depth = 0;
+                    let mut end = None;
+                    for (j, c) in chars.iter().enumerate().skip(i + 1) {
+                        match c {
+                            '{' => depth += 1,
+                            '}' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    end = Some(j);
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    let Some(end) = end else {
+                        self.error(
+                            line,
+                            start,
+                            "",
+                            InterpolationErrorKind::InvalidSyntax(
+                                "unterminated variable expression".to_string(),
+                            ),
+                        );
+                        result.extend(&chars[i..]);
+                        break;
+                    };
+
+                    let expression = chars[i + 2..end].iter().collect::<String>();
+                    let value = self.expand(&expression, line, start, start + 2);
+                    result.push_str(&value);
+                    i = end + 1;
+                }
+                Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                    let name = chars[i + 1..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                        .collect::<String>();
+
+                    match self.environment.get(&name) {
+                        Some(value) => result.push_str(value),
+                        None => self.error(line, start, &name, InterpolationErrorKind::Missing),
+                    }
+                    i += 1 + name.chars().count();
+                }
+                _ => {
+                    // A lone `$` is kept as is
+                    result.push('$');
+                    i += 1;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Expand the inside of a `${...}` expression.
+    fn expand(&mut self, expression: &str, line: usize, start: usize, column: usize) -> String {
+        let name_len = expression
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(expression.len());
+        let (name, operator) = expression.split_at(name_len);
+
+        if !is_valid_name(name) {
+            self.error(
+                line,
+                start,
+                name,
+                InterpolationErrorKind::InvalidSyntax(format!(
+                    "invalid variable expression: ${{{expression}}}"
+                )),
+            );
+            return String::new();
+        }
+
+        let value = self.environment.get(name).map(str::to_string);
+        let set = value.is_some();
+        let non_empty = value.as_deref().is_some_and(|v| !v.is_empty());
+
+        let (op, argument) = match operator {
+            "" => ("", ""),
+            op if op.starts_with(":-") || op.starts_with(":?") || op.starts_with(":+") => {
+                op.split_at(2)
+            }
+            op if op.starts_with('-') || op.starts_with('?') || op.starts_with('+') => {
+                op.split_at(1)
+            }
+            _ => {
+                self.error(
+                    line,
+                    start,
+                    name,
+                    InterpolationErrorKind::InvalidSyntax(format!(
+                        "invalid variable expression: ${{{expression}}}"
+                    )),
+                );
+                return String::new();
+            }
+        };
+
+        let argument_column = column + name.chars().count() + op.chars().count();
+
+        match op {
+            "" if set => value.unwrap_or_default(),
+            "" => {
+                self.error(line, start, name, InterpolationErrorKind::Missing);
+                String::new()
+            }
+            ":-" if non_empty => value.unwrap_or_default(),
+            "-" if set => value.unwrap_or_default(),
+            ":-" | "-" => self.interpolate(argument, line, argument_column),
+            ":?" if non_empty => value.unwrap_or_default(),
+            "?" if set => value.unwrap_or_default(),
+            ":?" | "?" => {
+                let message = self.interpolate(argument, line, argument_column);
+                self.error(line, start, name, InterpolationErrorKind::Required(message));
+                String::new()
+            }
+            ":+" if non_empty => self.interpolate(argument, line, argument_column),
+            "+" if set => self.interpolate(argument, line, argument_column),
+            _ => String::new(),
+        }
+    }
+}
+
+/// The path segment of a mapping key, as indexed by [Positions].
+fn key_segment(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        key => serde_yaml::to_string(key)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+impl Compose {
+    /// Load the variables available for interpolation, from the env files given to
+    /// the builder or `.env` in the project directory, and the process environment.
+    pub fn environment(&self) -> Result<Environment, ComposeError> {
+        let project_dir = self.project_dir();
+        let env_files = self.env_files.iter().map(PathBuf::from).collect::<Vec<_>>();
+        Environment::load(&project_dir, &env_files)
+    }
+
+    /// Interpolate and merge the compose files without invoking docker.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ComposeError::InterpolationError] with every unresolved variable of all files.
+    pub fn interpolate(&self) -> Result<String, ComposeError> {
+        let environment = self.environment()?;
+
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        for (path, content) in self.sources()? {
+            match interpolate(&content, path, &environment) {
+                Ok(content) => files.push(serde_yaml::from_str(&content)?),
+                Err(e) => errors.extend(e),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ComposeError::InterpolationError(errors));
+        }
+
+        Ok(serde_yaml::to_string(&merge::merge_all(files))?)
+    }
+
+    /// Parse the interpolated and merged compose files into a typed model.
+    pub fn interpolated_model(&self) -> Result<ComposeFile, ComposeError> {
+        self.interpolate()?.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn environment() -> Environment {
+        let mut environment = Environment::new();
+        environment.set("TAG", "16");
+        environment.set("EMPTY", "");
+        environment
+    }
+
+    fn parse(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let text = "image: postgres:${TAG}\n\
+                    a: ${EMPTY:-fallback} ${EMPTY-unused} ${UNSET-${TAG}}\n\
+                    b: $$TAG ${TAG:+set} $TAG\n\
+                    # ${IGNORED}\n\
+                    c: x # ${IGNORED}\n\
+                    d:\n  - ${TAG}\n  - \"${TAG}\"\n";
+
+        assert_eq!(
+            parse(&interpolate(text, None, &environment()).unwrap()),
+            parse(
+                "image: postgres:16\n\
+                 a: fallback  16\n\
+                 b: $TAG set 16\n\
+                 c: x\n\
+                 d: [16, '16']\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_interpolate_keeps_structure() {
+        let mut environment = Environment::new();
+        environment.set("A", "x: y");
+        environment.set("B", "# not a comment");
+        environment.set("C", "{q");
+
+        let interpolated = interpolate("a: ${A}\nb: ${B}\nc: ${C}\n", None, &environment).unwrap();
+
+        assert_eq!(
+            parse(&interpolated),
+            parse("a: 'x: y'\nb: '# not a comment'\nc: '{q'\n")
+        );
+    }
+
+    #[test]
+    fn test_interpolation_errors() {
+        let errors = interpolate(
+            "a: ${MISSING}\nb: x ${EMPTY:?must be set}\nc: ${UNCLOSED\nd: \"${MISSING}\" # ${ALSO_MISSING}\n",
+            Some(Path::new("compose.yml")),
+            &environment(),
+        )
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 4);
+        assert_eq!((errors[0].line, errors[0].column), (1, 4));
+        assert_eq!(errors[0].kind, InterpolationErrorKind::Missing);
+        assert_eq!((errors[1].line, errors[1].column), (2, 6));
+        assert_eq!(
+            errors[1].kind,
+            InterpolationErrorKind::Required("must be set".to_string())
+        );
+        assert!(matches!(
+            errors[2].kind,
+            InterpolationErrorKind::InvalidSyntax(_)
+        ));
+        assert_eq!((errors[3].line, errors[3].column), (4, 5));
+        assert_eq!(errors[3].variable, "MISSING");
+        assert_eq!(
+            errors[0].to_string(),
+            "compose.yml:1:4: variable MISSING is not set"
+        );
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let mut env = Environment::new();
+
+        env.parse_env_file(
+            "# comment\nexport A=1\nB=\"x\\ny\" \nC='${A}' \nD=${A}-$TAG # trailing\nTAG\n",
+            None,
+            &environment(),
+        )
+        .unwrap();
+
+        assert_eq!(env.get("A"), Some("1"));
+        assert_eq!(env.get("B"), Some("x\ny"));
+        assert_eq!(env.get("C"), Some("${A}"));
+        assert_eq!(env.get("D"), Some("1-16"));
+        assert_eq!(env.get("TAG"), Some("16"));
+    }
+}
@@ -1,15 +1,50 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub mod alert;
+mod endpoint;
+pub use endpoint::Endpoints;
 mod error;
-use command::{DownCommand, PsCommand, ScaleCommand, StartCommand, StatsCommand, UpCommand};
-pub use error::{ComposeBuilderError, ComposeError};
+pub mod fixture;
+pub mod graph;
+mod http;
+pub mod interpolation;
+pub mod lint;
+pub mod merge;
+use command::{
+    ConfigCommand, DownCommand, EventsCommand, InspectCommand, PsCommand, ScaleCommand,
+    StartCommand, StatsCommand, UpCommand,
+};
+pub use error::{CommandFailure, ComposeBuilderError, ComposeError, ComposeFileLocation};
 mod builder;
 pub use builder::ComposeBuilder;
 pub mod command;
 mod container;
+pub mod drift;
+pub use container::{Container, ContainerStatus, PortMapping, Status};
+pub mod model;
 mod parser;
-pub use command::ComposeCommand;
+pub mod plan;
+pub mod readiness;
+pub mod retry;
+pub mod schema;
+pub use command::{CancellationToken, ComposeCommand};
+#[cfg(feature = "macros")]
+pub use compose_rs_macros::compose_test;
 
 pub struct Compose {
-    path: String,
+    paths: Vec<String>,
+    project_name: Option<String>,
+    env_files: Vec<String>,
+    /// Compose file passed through stdin
+    yaml: Option<String>,
+    project_directory: Option<String>,
+    /// Compose file generated from a model, removed when the Compose is dropped
+    _generated: Option<tempfile::TempPath>,
+    retry: Option<retry::RetryPolicy>,
 }
 
 impl Compose {
@@ -22,38 +57,133 @@ impl Compose {
         builder.build()
     }
 
+    /// Create a Compose object from a model, e.g. built with [model::ComposeSpec].
+    ///
+    /// The model is written to a temporary file that is removed when the Compose is dropped.
+    /// Relative paths in the model are resolved against the temporary directory, and
+    /// without a `name` in the model the project is named after the temporary file,
+    /// so that every generated project gets its own name.
+    pub fn from_model(model: impl Into<model::ComposeFile>) -> Result<Self, ComposeBuilderError> {
+        Self::builder().model(model).build()
+    }
+
+    /// The project name passed to compose, if one was set.
+    pub fn project_name(&self) -> Option<&str> {
+        self.project_name.as_deref()
+    }
+
     fn init_command(&self) -> std::process::Command {
         let mut cmd = std::process::Command::new("docker");
-        cmd.arg("compose").arg("-f").arg(&self.path);
+        cmd.arg("compose");
+
+        for path in &self.paths {
+            cmd.arg("-f").arg(path);
+        }
+
+        if let Some(project_name) = &self.project_name {
+            cmd.arg("-p").arg(project_name);
+        }
+
+        for env_file in &self.env_files {
+            cmd.arg("--env-file").arg(env_file);
+        }
+
+        if let Some(project_directory) = &self.project_directory {
+            cmd.arg("--project-directory").arg(project_directory);
+        }
+
+        // Like `output()`, commands that are spawned do not read the parent's stdin
+        cmd.stdin(std::process::Stdio::null());
+
+        // The content is written to stdin when the command runs, see `exec_options`
+        if self.yaml.is_some() {
+            cmd.arg("-f").arg("-");
+        }
+
         cmd
     }
 
+    /// The options commands are run with unless they override them.
+    fn exec_options(&self) -> command::ExecOptions {
+        command::ExecOptions {
+            retry: self.retry.clone(),
+            stdin: self.yaml.as_deref().map(Arc::from),
+            ..Default::default()
+        }
+    }
+
+    /// The directory relative paths in the compose files are resolved against.
+    pub fn project_dir(&self) -> PathBuf {
+        match (&self.project_directory, self.paths.first()) {
+            (Some(dir), _) => PathBuf::from(dir),
+            (None, Some(path)) => Path::new(path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            _ => std::env::current_dir().unwrap_or_default(),
+        }
+    }
+
+    /// The content of each compose file in order, with its path if it is read from disk.
+    fn sources(&self) -> Result<Vec<(Option<&Path>, String)>, ComposeError> {
+        let mut sources = self
+            .paths
+            .iter()
+            .map(|path| Ok((Some(Path::new(path.as_str())), fs::read_to_string(path)?)))
+            .collect::<Result<Vec<_>, ComposeError>>()?;
+
+        if let Some(yaml) = &self.yaml {
+            sources.push((None, yaml.clone()));
+        }
+
+        Ok(sources)
+    }
+
     pub fn up(&self) -> UpCommand {
-        UpCommand::new(self.init_command())
+        UpCommand::new(self.init_command()).with_options(self.exec_options())
     }
 
     pub fn down(&self) -> DownCommand {
-        DownCommand::new(self.init_command())
+        DownCommand::new(self.init_command()).with_options(self.exec_options())
     }
 
     pub fn ps(&self) -> PsCommand {
-        PsCommand::new(self.init_command())
+        PsCommand::new(self.init_command()).with_options(self.exec_options())
     }
 
     pub fn scale(&self) -> ScaleCommand {
-        ScaleCommand::new(self.init_command())
+        ScaleCommand::new(self.init_command()).with_options(self.exec_options())
     }
 
     pub fn stats(&self) -> StatsCommand {
-        StatsCommand::new(self.init_command())
+        StatsCommand::new(self.init_command()).with_options(self.exec_options())
     }
 
     pub fn start(&self) -> StartCommand {
-        StartCommand::new(self.init_command())
+        StartCommand::new(self.init_command()).with_options(self.exec_options())
+    }
+
+    pub fn events(&self) -> EventsCommand {
+        EventsCommand::new(self.init_command()).with_options(self.exec_options())
+    }
+
+    pub fn config(&self) -> ConfigCommand {
+        ConfigCommand::new(self.init_command()).with_options(self.exec_options())
+    }
+
+    /// Inspect the containers of a service, more services can be added with
+    /// [InspectCommand::service].
+    pub fn inspect(&self, service: &str) -> InspectCommand {
+        InspectCommand::new(self.init_command())
+            .with_options(self.exec_options())
+            .service(service)
     }
 }
 
 pub mod prelude {
+    pub use crate::fixture::ComposeFixture;
+    pub use crate::readiness::{Probe, Readiness};
+    pub use crate::retry::RetryPolicy;
     pub use crate::Compose;
     pub use crate::ComposeBuilder;
     pub use crate::ComposeBuilderError;
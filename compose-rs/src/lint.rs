@@ -0,0 +1,619 @@
+//! Security and best-practice lint rules for compose files.
+//!
+//! ```rust,no_run
+//! use compose_rs::{lint::{Linter, Rule, Severity}, Compose};
+//!
+//! let compose = Compose::builder().path("docker-compose.yml").build().unwrap();
+//!
+//! let diagnostics = Linter::new()
+//!     .disable(Rule::MissingMemoryLimit)
+//!     .severity(Rule::LatestTag, Severity::Error)
+//!     .lint_compose(&compose)
+//!     .unwrap();
+//!
+//! for diagnostic in &diagnostics {
+//!     eprintln!("{diagnostic}");
+//! }
+//! println!("{}", compose_rs::lint::to_sarif(&diagnostics, &compose.project_dir()));
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use regex::Regex;
+use serde_json::json;
+
+use crate::{
+    model::{ComposeFile, Service},
+    schema::Positions,
+    Compose, ComposeError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// The service runs with `privileged: true`
+    Privileged,
+    /// The service shares the network namespace of the host
+    HostNetwork,
+    /// The image is untagged or uses the `latest` tag
+    LatestTag,
+    /// The service has no healthcheck
+    MissingHealthcheck,
+    /// The service has no memory limit
+    MissingMemoryLimit,
+    /// A secret, like a password or token, is set as a plain environment variable
+    PlainSecretEnv,
+    /// The Docker socket is mounted into the container
+    DockerSocket,
+}
+
+impl Rule {
+    /// All rules, in the order they are checked.
+    pub const ALL: [Rule; 7] = [
+        Rule::Privileged,
+        Rule::HostNetwork,
+        Rule::LatestTag,
+        Rule::MissingHealthcheck,
+        Rule::MissingMemoryLimit,
+        Rule::PlainSecretEnv,
+        Rule::DockerSocket,
+    ];
+
+    /// The identifier of the rule, e.g. `latest-tag`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::Privileged => "privileged",
+            Rule::HostNetwork => "host-network",
+            Rule::LatestTag => "latest-tag",
+            Rule::MissingHealthcheck => "missing-healthcheck",
+            Rule::MissingMemoryLimit => "missing-memory-limit",
+            Rule::PlainSecretEnv => "plain-secret-env",
+            Rule::DockerSocket => "docker-socket",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Rule::Privileged => "Services should not run privileged",
+            Rule::HostNetwork => "Services should not use the host network",
+            Rule::LatestTag => "Images should be pinned to a tag other than latest",
+            Rule::MissingHealthcheck => "Services should define a healthcheck",
+            Rule::MissingMemoryLimit => "Services should define a memory limit",
+            Rule::PlainSecretEnv => "Secrets should not be passed as plain environment variables",
+            Rule::DockerSocket => "The Docker socket should not be mounted into containers",
+        }
+    }
+
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Rule::Privileged | Rule::PlainSecretEnv | Rule::DockerSocket => Severity::Error,
+            Rule::HostNetwork | Rule::LatestTag | Rule::MissingMemoryLimit => Severity::Warning,
+            Rule::MissingHealthcheck => Severity::Info,
+        }
+    }
+
+    fn check(&self, name: &str, service: &Service) -> Vec<(Vec<String>, String)> {
+        let path = |keys: &[&str]| {
+            ["services", name]
+                .iter()
+                .chain(keys)
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        match self {
+            Rule::Privileged if service.privileged == Some(true) => vec![(
+                path(&["privileged"]),
+                format!("Service {name} runs privileged"),
+            )],
+            Rule::HostNetwork if service.network_mode.as_deref() == Some("host") => vec![(
+                path(&["network_mode"]),
+                format!("Service {name} uses the host network"),
+            )],
+            Rule::LatestTag => match &service.image {
+                Some(image) if is_latest(image) => vec![(
+                    path(&["image"]),
+                    format!("Image {image} of service {name} is not pinned to a tag"),
+                )],
+                _ => Vec::new(),
+            },
+            Rule::MissingHealthcheck => match &service.healthcheck {
+                Some(healthcheck) if healthcheck.disable != Some(true) => Vec::new(),
+                _ => vec![(path(&[]), format!("Service {name} has no healthcheck"))],
+            },
+            Rule::MissingMemoryLimit => {
+                let limit = service
+                    .deploy
+                    .as_ref()
+                    .and_then(|deploy| deploy.resources.as_ref())
+                    .and_then(|resources| resources.limits.as_ref())
+                    .and_then(|limits| limits.memory.as_ref());
+
+                match limit.is_some() || service.extra.contains_key("mem_limit") {
+                    true => Vec::new(),
+                    false => vec![(path(&[]), format!("Service {name} has no memory limit"))],
+                }
+            }
+            Rule::PlainSecretEnv => service
+                .environment
+                .iter()
+                .filter(|(key, value)| is_secret(key, value.as_deref()))
+                .map(|(key, _)| {
+                    (
+                        path(&["environment", key]),
+                        format!(
+                            "Service {name} sets the secret {key} as plain environment variable"
+                        ),
+                    )
+                })
+                .collect(),
+            Rule::DockerSocket => service
+                .volumes
+                .iter()
+                .enumerate()
+                .filter(|(_, volume)| {
+                    volume
+                        .source
+                        .as_deref()
+                        .is_some_and(|source| source.ends_with("docker.sock"))
+                })
+                .map(|(index, volume)| {
+                    (
+                        path(&["volumes", &index.to_string()]),
+                        format!(
+                            "Service {name} mounts the Docker socket at {}",
+                            volume.target
+                        ),
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// Whether an image reference has no tag or the `latest` tag, digests are pinned.
+fn is_latest(image: &str) -> bool {
+    if image.contains('@') {
+        return false;
+    }
+
+    // The registry may contain a port, the tag follows the last path segment
+    let name = image.rsplit('/').next().unwrap_or(image);
+    match name.split_once(':') {
+        Some((_, tag)) => tag == "latest",
+        None => true,
+    }
+}
+
+/// Whether an environment variable looks like a secret with a literal value.
+fn is_secret(key: &str, value: Option<&str>) -> bool {
+    static SECRET: OnceLock<Regex> = OnceLock::new();
+    let secret = SECRET.get_or_init(|| {
+        Regex::new(r"(?i)(PASSWORD|PASSWD|SECRET|TOKEN|API_?KEY|PRIVATE_?KEY|CREDENTIAL)").unwrap()
+    });
+
+    // `_FILE` variables point to a mounted secret, `$` values are interpolated
+    let literal = value.is_some_and(|value| !value.is_empty() && !value.contains('$'));
+    literal && secret.is_match(key) && !key.to_uppercase().ends_with("_FILE")
+}
+
+/// Where a diagnostic was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: Option<PathBuf>,
+    /// YAML path, e.g. `services.api.image`
+    pub path: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    pub service: String,
+    pub location: Location,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.location.file {
+            write!(f, "{}:", file.display())?;
+        }
+        if let (Some(line), Some(column)) = (self.location.line, self.location.column) {
+            write!(f, "{line}:{column}:")?;
+        }
+
+        write!(
+            f,
+            " {}[{}]: {}: {}",
+            self.severity, self.rule, self.location.path, self.message
+        )
+    }
+}
+
+/// Runs the lint rules, all rules are enabled with their default severity.
+#[derive(Debug, Clone, Default)]
+pub struct Linter {
+    overrides: HashMap<Rule, Option<Severity>>,
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn disable(mut self, rule: Rule) -> Self {
+        self.overrides.insert(rule, None);
+        self
+    }
+
+    /// Report findings of `rule` with `severity` instead of its default.
+    pub fn severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.overrides.insert(rule, Some(severity));
+        self
+    }
+
+    /// The rules that are enabled, with their severity.
+    pub fn rules(&self) -> impl Iterator<Item = (Rule, Severity)> + '_ {
+        Rule::ALL.into_iter().filter_map(|rule| {
+            match self.overrides.get(&rule) {
+                Some(severity) => *severity,
+                None => Some(rule.default_severity()),
+            }
+            .map(|severity| (rule, severity))
+        })
+    }
+
+    /// Lint a model, the diagnostics have no file positions.
+    pub fn lint(&self, model: &ComposeFile) -> Vec<Diagnostic> {
+        self.findings(model)
+            .into_iter()
+            .map(|(segments, diagnostic)| Diagnostic {
+                location: Location {
+                    file: None,
+                    path: segments.join("."),
+                    line: None,
+                    column: None,
+                },
+                ..diagnostic
+            })
+            .collect()
+    }
+
+    /// Lint the merged compose files, diagnostics point at the file that defines the value.
+    pub fn lint_compose(&self, compose: &Compose) -> Result<Vec<Diagnostic>, ComposeError> {
+        let model = compose.model()?;
+        let sources = compose
+            .sources()?
+            .into_iter()
+            .map(|(path, content)| (path.map(|p| p.to_path_buf()), Positions::index(&content)))
+            .collect::<Vec<_>>();
+
+        Ok(self
+            .findings(&model)
+            .into_iter()
+            .map(|(segments, diagnostic)| {
+                // The most specific position, later files win ties since they override
+                let found = sources
+                    .iter()
+                    .filter_map(|(file, positions)| Some((file, positions.closest(&segments)?)))
+                    .max_by_key(|(_, (len, _))| *len);
+
+                Diagnostic {
+                    location: Location {
+                        file: found.and_then(|(file, _)| file.clone()),
+                        path: segments.join("."),
+                        line: found.map(|(_, (_, (line, _)))| line),
+                        column: found.map(|(_, (_, (_, column)))| column),
+                    },
+                    ..diagnostic
+                }
+            })
+            .collect())
+    }
+
+    fn findings(&self, model: &ComposeFile) -> Vec<(Vec<String>, Diagnostic)> {
+        let mut findings = Vec::new();
+
+        for (name, service) in &model.services {
+            for (rule, severity) in self.rules() {
+                for (segments, message) in rule.check(name, service) {
+                    let diagnostic = Diagnostic {
+                        rule,
+                        severity,
+                        message,
+                        service: name.clone(),
+                        location: Location {
+                            file: None,
+                            path: String::new(),
+                            line: None,
+                            column: None,
+                        },
+                    };
+                    findings.push((segments, diagnostic));
+                }
+            }
+        }
+
+        findings
+    }
+}
+
+impl Compose {
+    /// Lint the compose files with all rules enabled, see [Linter] to configure the rules.
+    pub fn lint(&self) -> Result<Vec<Diagnostic>, ComposeError> {
+        Linter::new().lint_compose(self)
+    }
+}
+
+/// The base id file locations in SARIF logs are relative to.
+const SRCROOT: &str = "%SRCROOT%";
+
+/// Render diagnostics as a SARIF 2.1.0 log for code-scanning tools.
+///
+/// Files inside `root`, usually [Compose::project_dir], are referenced relative to the
+/// `%SRCROOT%` base id, other files by their absolute `file://` URI.
+pub fn to_sarif(diagnostics: &[Diagnostic], root: &Path) -> serde_json::Value {
+    let level = |severity: Severity| match severity {
+        Severity::Info => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+
+    let rules = Rule::ALL
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id(),
+                "shortDescription": { "text": rule.description() },
+                "defaultConfiguration": { "level": level(rule.default_severity()) },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut location = json!({
+                "logicalLocations": [{ "fullyQualifiedName": diagnostic.location.path }],
+            });
+
+            if let Some(file) = &diagnostic.location.file {
+                let artifact = match file.strip_prefix(root) {
+                    Ok(relative) => json!({ "uri": uri_path(relative), "uriBaseId": SRCROOT }),
+                    Err(_) => json!({ "uri": file_uri(file) }),
+                };
+                let mut physical = json!({ "artifactLocation": artifact });
+                if let (Some(line), Some(column)) =
+                    (diagnostic.location.line, diagnostic.location.column)
+                {
+                    physical["region"] = json!({ "startLine": line, "startColumn": column });
+                }
+                location["physicalLocation"] = physical;
+            }
+
+            json!({
+                "ruleId": diagnostic.rule.id(),
+                "ruleIndex": Rule::ALL.iter().position(|rule| *rule == diagnostic.rule),
+                "level": level(diagnostic.severity),
+                "message": { "text": diagnostic.message },
+                "locations": [location],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "originalUriBaseIds": {
+                SRCROOT: { "uri": format!("{}/", file_uri(root).trim_end_matches('/')) },
+            },
+            "results": results,
+        }]
+    })
+}
+
+/// The absolute `file://` URI of a path.
+fn file_uri(path: &Path) -> String {
+    format!("file:///{}", uri_path(path))
+}
+
+/// The percent-encoded segments of a path joined with forward slashes.
+fn uri_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            // Drive letters like `C:` are kept as they are
+            Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().to_string()),
+            Component::Normal(segment) => Some(percent_encode(&segment.to_string_lossy())),
+            Component::ParentDir => Some("..".to_string()),
+            Component::RootDir | Component::CurDir => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE_FILE: &str = r#"
+services:
+  api:
+    image: registry.local:5000/api
+    privileged: true
+    network_mode: host
+    environment:
+      DB_PASSWORD: hunter2
+      API_TOKEN: ${API_TOKEN}
+      TOKEN_FILE: /run/secrets/token
+    volumes:
+      - /var/run/docker.sock:/var/run/docker.sock
+  db:
+    image: postgres:16
+    healthcheck:
+      test: ["CMD", "pg_isready"]
+    deploy:
+      resources:
+        limits:
+          memory: 512M
+"#;
+
+    #[test]
+    fn test_lint() {
+        let model: ComposeFile = COMPOSE_FILE.parse().unwrap();
+        let diagnostics = Linter::new()
+            .disable(Rule::MissingHealthcheck)
+            .severity(Rule::LatestTag, Severity::Error)
+            .lint(&model);
+
+        let rules = diagnostics.iter().map(|d| d.rule).collect::<Vec<_>>();
+        assert_eq!(
+            rules,
+            [
+                Rule::Privileged,
+                Rule::HostNetwork,
+                Rule::LatestTag,
+                Rule::MissingMemoryLimit,
+                Rule::PlainSecretEnv,
+                Rule::DockerSocket,
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.service == "api"));
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+        assert_eq!(
+            diagnostics[4].location.path,
+            "services.api.environment.DB_PASSWORD"
+        );
+
+        assert!(is_latest("nginx"));
+        assert!(is_latest("nginx:latest"));
+        assert!(!is_latest("registry.local:5000/nginx:1.25"));
+        assert!(!is_latest("nginx@sha256:abc"));
+
+        let sarif = to_sarif(&diagnostics, Path::new("/project"));
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[0]["ruleId"], "privileged");
+        assert_eq!(results[3]["level"], "warning");
+    }
+
+    #[test]
+    fn test_lint_positions() {
+        let compose = Compose::builder().yaml(COMPOSE_FILE).build().unwrap();
+        let diagnostics = compose.lint().unwrap();
+
+        let privileged = diagnostics
+            .iter()
+            .find(|d| d.rule == Rule::Privileged)
+            .unwrap();
+        assert_eq!(privileged.location.line, Some(5));
+        assert_eq!(privileged.location.column, Some(5));
+
+        let secret = diagnostics
+            .iter()
+            .find(|d| d.rule == Rule::PlainSecretEnv)
+            .unwrap();
+        assert_eq!(secret.location.line, Some(8));
+
+        let socket = diagnostics
+            .iter()
+            .find(|d| d.rule == Rule::DockerSocket)
+            .unwrap();
+        assert_eq!(socket.location.path, "services.api.volumes.0");
+        assert_eq!(
+            (socket.location.line, socket.location.column),
+            (Some(12), Some(9))
+        );
+    }
+
+    #[test]
+    fn test_sarif_locations() {
+        let diagnostic = |file: &str| Diagnostic {
+            rule: Rule::Privileged,
+            severity: Severity::Error,
+            message: String::new(),
+            service: "api".to_string(),
+            location: Location {
+                file: Some(PathBuf::from(file)),
+                path: "services.api.privileged".to_string(),
+                line: Some(5),
+                column: Some(5),
+            },
+        };
+
+        let sarif = to_sarif(
+            &[
+                diagnostic("/project/deploy dir/compose.yml"),
+                diagnostic("/other/compose.yml"),
+            ],
+            Path::new("/project"),
+        );
+        let run = &sarif["runs"][0];
+        let location = |i: usize| &run["results"][i]["locations"][0]["physicalLocation"];
+
+        assert_eq!(
+            run["originalUriBaseIds"]["%SRCROOT%"]["uri"],
+            "file:///project/"
+        );
+        assert_eq!(
+            location(0)["artifactLocation"],
+            json!({ "uri": "deploy%20dir/compose.yml", "uriBaseId": "%SRCROOT%" })
+        );
+        assert_eq!(
+            location(1)["artifactLocation"],
+            json!({ "uri": "file:///other/compose.yml" })
+        );
+        assert_eq!(location(0)["region"]["startLine"], 5);
+    }
+}
@@ -0,0 +1,369 @@
+//! Merging of multiple compose files following the compose-spec merge rules.
+//!
+//! Mappings are merged recursively and scalars are replaced. Sequences are appended,
+//! except for the keys the specification treats specially: `command`, `entrypoint`
+//! and `healthcheck.test` are replaced, `ports`, `expose`, `dns` and similar keep unique
+//! values, `volumes` and `devices` are merged by mount target, `secrets` and `configs`
+//! by target, and `environment`, `labels`, `build.args`, `depends_on` and `networks` are
+//! merged as mappings regardless of their syntax. The `!reset` tag removes a value and the
+//! `!override` tag replaces it without merging.
+
+use serde_yaml::{value::TaggedValue, Mapping, Value};
+
+use crate::model::ServiceVolume;
+
+/// Merge `other` into `base`, the values of `other` take precedence.
+pub fn merge(base: Value, other: Value) -> Value {
+    merge_value(Some(base), other, &[]).unwrap_or(Value::Null)
+}
+
+/// Merge a list of compose files, later files take precedence.
+pub fn merge_all(files: impl IntoIterator<Item = Value>) -> Value {
+    let mut files = files.into_iter();
+
+    let first = files.next().unwrap_or(Value::Mapping(Mapping::new()));
+    let first = resolve_tags(first).unwrap_or(Value::Null);
+
+    files.fold(first, merge)
+}
+
+fn merge_value(base: Option<Value>, other: Value, path: &[&str]) -> Option<Value> {
+    let other = match other {
+        Value::Tagged(tagged) => match tagged.tag.to_string().as_str() {
+            "!reset" => return None,
+            "!override" => return resolve_tags(tagged.value),
+            _ => Value::Tagged(tagged),
+        },
+        other => other,
+    };
+
+    let Some(base) = base else {
+        return resolve_tags(other);
+    };
+
+    // Keys of a service, e.g. `services.api.ports`
+    let service_key = match path {
+        ["services", _, key] => Some(*key),
+        _ => None,
+    };
+
+    let merged = match (service_key, base, other) {
+        (Some("command" | "entrypoint"), _, other) => return resolve_tags(other),
+        (Some("environment" | "labels" | "sysctls"), base, other) => {
+            merge_mappings(key_values(base), key_values(other), path)
+        }
+        (Some("depends_on"), base, other) => {
+            merge_mappings(dependencies(base), dependencies(other), path)
+        }
+        (Some("networks"), base, other) => merge_mappings(names(base), names(other), path),
+        (Some("build"), base, other) => merge_mappings(build(base), build(other), path),
+        (
+            Some(
+                "ports" | "expose" | "external_links" | "dns" | "dns_search" | "tmpfs" | "links"
+                | "cap_add" | "cap_drop" | "extra_hosts" | "dns_opt" | "env_file" | "profiles",
+            ),
+            base,
+            other,
+        ) => merge_unique(base, other),
+        (Some("volumes"), base, other) => merge_keyed(base, other, mount_target),
+        (Some("devices"), base, other) => merge_keyed(base, other, device_target),
+        (Some("secrets" | "configs"), base, other) => merge_keyed(base, other, file_target),
+        (_, base, other) => match (base, other) {
+            (_, other) if path.ends_with(&["healthcheck", "test"]) => return resolve_tags(other),
+            (base, other) if path.ends_with(&["build", "args"]) => {
+                merge_mappings(key_values(base), key_values(other), path)
+            }
+            (Value::Mapping(base), Value::Mapping(other)) => merge_mappings(base, other, path),
+            (Value::Sequence(mut base), Value::Sequence(other)) => {
+                base.extend(other.into_iter().filter_map(resolve_tags));
+                Value::Sequence(base)
+            }
+            (_, other) => return resolve_tags(other),
+        },
+    };
+
+    Some(merged)
+}
+
+fn merge_mappings(mut base: Mapping, other: Mapping, path: &[&str]) -> Value {
+    for (key, value) in other {
+        let existing = base.remove(&key);
+
+        let mut child = path.to_vec();
+        child.push(key.as_str().unwrap_or_default());
+
+        if let Some(merged) = merge_value(existing, value, &child) {
+            base.insert(key, merged);
+        }
+    }
+
+    Value::Mapping(base)
+}
+
+fn merge_unique(base: Value, other: Value) -> Value {
+    let mut merged = sequence(base);
+
+    for value in sequence(other).into_iter().filter_map(resolve_tags) {
+        if !merged.contains(&value) {
+            merged.push(value);
+        }
+    }
+
+    Value::Sequence(merged)
+}
+
+/// Merge sequences by a key, entries of `other` replace entries of `base` with the same key.
+fn merge_keyed(base: Value, other: Value, key: fn(&Value) -> Option<String>) -> Value {
+    let mut merged = sequence(base);
+
+    for value in sequence(other).into_iter().filter_map(resolve_tags) {
+        let position = key(&value).and_then(|target| {
+            merged
+                .iter()
+                .position(|existing| key(existing).as_ref() == Some(&target))
+        });
+
+        match position {
+            Some(position) => merged[position] = value,
+            None => merged.push(value),
+        }
+    }
+
+    Value::Sequence(merged)
+}
+
+fn sequence(value: Value) -> Vec<Value> {
+    match value {
+        Value::Sequence(sequence) => sequence,
+        Value::Null => Vec::new(),
+        value => vec![value],
+    }
+}
+
+fn mount_target(value: &Value) -> Option<String> {
+    match value {
+        Value::String(spec) => ServiceVolume::parse_short(spec).ok().map(|v| v.target),
+        Value::Mapping(volume) => volume.get("target")?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+fn device_target(value: &Value) -> Option<String> {
+    match value {
+        // `host_path[:container_path[:permissions]]`
+        Value::String(spec) => {
+            let mut parts = spec.split(':');
+            let host = parts.next()?;
+            Some(parts.next().unwrap_or(host).to_string())
+        }
+        Value::Mapping(device) => device.get("target")?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+fn file_target(value: &Value) -> Option<String> {
+    match value {
+        Value::String(source) => Some(source.clone()),
+        Value::Mapping(reference) => reference
+            .get("target")
+            .or_else(|| reference.get("source"))?
+            .as_str()
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Normalize a list of `KEY=VALUE` strings into a mapping.
+fn key_values(value: Value) -> Mapping {
+    match value {
+        Value::Mapping(mapping) => mapping,
+        Value::Sequence(sequence) => sequence
+            .into_iter()
+            .filter_map(|entry| {
+                let entry = entry.as_str()?.to_string();
+                Some(match entry.split_once('=') {
+                    Some((key, value)) => (key.into(), value.into()),
+                    None => (entry.into(), Value::Null),
+                })
+            })
+            .collect(),
+        _ => Mapping::new(),
+    }
+}
+
+/// Normalize the list syntax of `depends_on` into a mapping.
+fn dependencies(value: Value) -> Mapping {
+    match value {
+        Value::Sequence(sequence) => sequence
+            .into_iter()
+            .map(|service| {
+                let mut dependency = Mapping::new();
+                dependency.insert("condition".into(), "service_started".into());
+                (service, Value::Mapping(dependency))
+            })
+            .collect(),
+        value => names(value),
+    }
+}
+
+/// Normalize a list of names into a mapping with empty values.
+fn names(value: Value) -> Mapping {
+    match value {
+        Value::Mapping(mapping) => mapping,
+        Value::Sequence(sequence) => sequence
+            .into_iter()
+            .map(|name| (name, Value::Null))
+            .collect(),
+        _ => Mapping::new(),
+    }
+}
+
+/// Normalize the short syntax of `build` into a mapping.
+fn build(value: Value) -> Mapping {
+    match value {
+        Value::String(context) => {
+            let mut build = Mapping::new();
+            build.insert("context".into(), context.into());
+            build
+        }
+        value => names(value),
+    }
+}
+
+/// Apply the merge tags of a value that is not merged with anything.
+fn resolve_tags(value: Value) -> Option<Value> {
+    match value {
+        Value::Tagged(tagged) => match tagged.tag.to_string().as_str() {
+            "!reset" => None,
+            "!override" => resolve_tags(tagged.value),
+            _ => Some(Value::Tagged(Box::new(TaggedValue {
+                tag: tagged.tag,
+                value: resolve_tags(tagged.value)?,
+            }))),
+        },
+        Value::Mapping(mapping) => Some(Value::Mapping(
+            mapping
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, resolve_tags(value)?)))
+                .collect(),
+        )),
+        Value::Sequence(sequence) => Some(Value::Sequence(
+            sequence.into_iter().filter_map(resolve_tags).collect(),
+        )),
+        value => Some(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = yaml(
+            r#"
+services:
+  api:
+    image: api:1
+    command: ["serve"]
+    environment:
+      - A=1
+      - B=2
+    ports: ["8080:80"]
+    volumes:
+      - data:/data
+      - ./config:/etc/api
+    depends_on: [db]
+    labels:
+      team: core
+"#,
+        );
+        let other = yaml(
+            r#"
+services:
+  api:
+    image: api:2
+    command: ["serve", "--debug"]
+    environment:
+      B: "3"
+    ports: ["8080:80", "9000:9000"]
+    volumes:
+      - ./override:/etc/api:ro
+    depends_on:
+      cache:
+        condition: service_healthy
+    labels: !reset {}
+    networks: !override [backend]
+"#,
+        );
+
+        let merged = merge(base, other);
+        let expected = yaml(
+            r#"
+services:
+  api:
+    image: api:2
+    command: ["serve", "--debug"]
+    environment:
+      A: "1"
+      B: "3"
+    ports: ["8080:80", "9000:9000"]
+    volumes:
+      - data:/data
+      - ./override:/etc/api:ro
+    depends_on:
+      db:
+        condition: service_started
+      cache:
+        condition: service_healthy
+    networks: [backend]
+"#,
+        );
+
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn test_merge_build_args() {
+        let base = yaml(
+            r#"
+services:
+  api:
+    build:
+      context: .
+      args:
+        - VERSION=1
+        - TARGET=release
+"#,
+        );
+        let other = yaml(
+            r#"
+services:
+  api:
+    build:
+      args:
+        VERSION: "2"
+        FEATURES: full
+"#,
+        );
+
+        let expected = yaml(
+            r#"
+services:
+  api:
+    build:
+      context: .
+      args:
+        VERSION: "2"
+        TARGET: release
+        FEATURES: full
+"#,
+        );
+
+        assert_eq!(merge(base, other), expected);
+    }
+}
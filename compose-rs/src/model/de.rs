@@ -0,0 +1,140 @@
+//! Deserialize helpers for the alternative syntaxes compose files allow.
+
+use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::ComposeError;
+
+/// A YAML scalar, compose accepts numbers and booleans wherever it expects a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    String(String),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl Scalar {
+    fn into_string(self) -> String {
+        match self {
+            Scalar::String(value) => value,
+            Scalar::Int(value) => value.to_string(),
+            Scalar::UInt(value) => value.to_string(),
+            Scalar::Float(value) => value.to_string(),
+            Scalar::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+/// Report an error of a short syntax parser without the prefix of [ComposeError::ParseError].
+pub(crate) fn parse_error<E: serde::de::Error>(error: ComposeError) -> E {
+    match error {
+        ComposeError::ParseError(message) => E::custom(message),
+        error => E::custom(error),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyValues {
+    List(Vec<String>),
+    Map(IndexMap<String, Option<Scalar>>),
+}
+
+/// A list of `KEY=VALUE` strings or a mapping, values without `=` or `null` are `None`.
+pub(crate) fn key_values<'de, D>(
+    deserializer: D,
+) -> Result<IndexMap<String, Option<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<KeyValues>::deserialize(deserializer)? {
+        None => IndexMap::new(),
+        Some(KeyValues::List(list)) => list
+            .into_iter()
+            .map(|entry| match entry.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (entry, None),
+            })
+            .collect(),
+        Some(KeyValues::Map(map)) => map
+            .into_iter()
+            .map(|(key, value)| (key, value.map(Scalar::into_string)))
+            .collect(),
+    })
+}
+
+/// Like [key_values], but missing values are empty strings.
+pub(crate) fn labels<'de, D>(deserializer: D) -> Result<IndexMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(key_values(deserializer)?
+        .into_iter()
+        .map(|(key, value)| (key, value.unwrap_or_default()))
+        .collect())
+}
+
+pub(crate) fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<Scalar>::deserialize(deserializer)?.map(Scalar::into_string))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+/// A single value or a list of values.
+pub(crate) fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    Ok(match Option::<OneOrMany<T>>::deserialize(deserializer)? {
+        None => Vec::new(),
+        Some(OneOrMany::One(value)) => vec![value],
+        Some(OneOrMany::Many(values)) => values,
+    })
+}
+
+/// A mapping whose values may be `null`, e.g. `networks: { default: }`.
+pub(crate) fn nullable_map<'de, D, T>(deserializer: D) -> Result<IndexMap<String, T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + Default,
+{
+    Ok(
+        Option::<IndexMap<String, Option<T>>>::deserialize(deserializer)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| (key, value.unwrap_or_default()))
+            .collect(),
+    )
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum External {
+    Bool(bool),
+    // Legacy syntax: `external: { name: ... }`
+    Named(serde::de::IgnoredAny),
+}
+
+pub(crate) fn external<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        Option::<External>::deserialize(deserializer)?.map(|external| match external {
+            External::Bool(value) => value,
+            External::Named(_) => true,
+        }),
+    )
+}
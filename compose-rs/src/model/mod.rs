@@ -0,0 +1,275 @@
+//! A typed model of the compose file format.
+//!
+//! Alternative syntaxes, like the short syntax of ports and volumes or environment lists,
+//! are normalized to their long form on parse.
+
+use std::{fs, path::Path, str::FromStr};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{merge, Compose, ComposeError};
+
+mod de;
+mod port;
+pub use port::Port;
+mod service;
+pub use service::{
+    Build, Command, Dependency, Deploy, FileReference, Healthcheck, ResourceLimits, Resources,
+    Service, ServiceNetwork, ServiceVolume,
+};
+mod spec;
+pub use spec::{ComposeSpec, HealthcheckSpec, ServiceSpec};
+
+/// A compose file.
+///
+/// Top-level keys that are not modelled explicitly, like `x-` extensions,
+/// are kept in [ComposeFile::extra].
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ComposeFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::nullable_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub services: IndexMap<String, Service>,
+    #[serde(
+        default,
+        deserialize_with = "de::nullable_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub networks: IndexMap<String, Network>,
+    #[serde(
+        default,
+        deserialize_with = "de::nullable_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub volumes: IndexMap<String, Volume>,
+    #[serde(
+        default,
+        deserialize_with = "de::nullable_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub secrets: IndexMap<String, Secret>,
+    #[serde(
+        default,
+        deserialize_with = "de::nullable_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub configs: IndexMap<String, Config>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+impl ComposeFile {
+    /// Read and parse the compose file at `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ComposeError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(ComposeError::FileNotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Serialize the model to YAML, alternative syntaxes are written in their long form.
+    pub fn to_yaml(&self) -> Result<String, ComposeError> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+}
+
+impl FromStr for ComposeFile {
+    type Err = ComposeError;
+
+    fn from_str(yaml: &str) -> Result<Self, Self::Err> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Network {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::external",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub external: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub internal: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "de::labels",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub labels: IndexMap<String, String>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Volume {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::external",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub external: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "de::labels",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub labels: IndexMap<String, String>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Secret {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::external",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub external: Option<bool>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::external",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub external: Option<bool>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+impl Compose {
+    /// Parse the compose files into a typed model, without interpolating variables.
+    ///
+    /// Multiple files are merged following the compose-spec merge rules, see [crate::merge].
+    pub fn model(&self) -> Result<ComposeFile, ComposeError> {
+        let files = self
+            .sources()?
+            .into_iter()
+            .map(|(_, content)| Ok(serde_yaml::from_str(&content)?))
+            .collect::<Result<Vec<serde_yaml::Value>, ComposeError>>()?;
+
+        Ok(serde_yaml::from_value(merge::merge_all(files))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE_FILE: &str = r#"
+services:
+  api:
+    build: ./api
+    command: ["serve", "--port", "8080"]
+    environment:
+      - RUST_LOG=info
+      - DATABASE_URL
+    ports:
+      - "8080:8080"
+      - "127.0.0.1:9000-9001:9000-9001/udp"
+      - target: 80
+        published: 8081
+    volumes:
+      - ./config:/etc/api:ro
+      - data:/var/lib/api
+    depends_on:
+      db:
+        condition: service_healthy
+    networks: [backend]
+    secrets: [token]
+    deploy:
+      resources:
+        limits:
+          memory: 512M
+          cpus: 0.5
+  db:
+    image: postgres:16
+    environment:
+      POSTGRES_PASSWORD: secret
+      POSTGRES_PORT: 5432
+    healthcheck:
+      test: ["CMD", "pg_isready"]
+      interval: 5s
+    stop_grace_period: 1m
+networks:
+  backend:
+volumes:
+  data:
+secrets:
+  token:
+    file: ./token.txt
+x-common:
+  restart: always
+"#;
+
+    #[test]
+    fn test_parse_model() {
+        let model: ComposeFile = COMPOSE_FILE.parse().unwrap();
+
+        let api = &model.services["api"];
+        assert_eq!(api.build.as_ref().unwrap().context, "./api");
+        assert_eq!(api.environment["RUST_LOG"].as_deref(), Some("info"));
+        assert_eq!(api.environment["DATABASE_URL"], None);
+        assert_eq!(api.ports.len(), 4);
+        assert_eq!(api.ports[2].published.as_deref(), Some("9001"));
+        assert_eq!(api.ports[2].host_ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(api.ports[3].published.as_deref(), Some("8081"));
+        assert_eq!(api.volumes[0].kind, "bind");
+        assert_eq!(api.volumes[0].read_only, Some(true));
+        assert_eq!(api.volumes[1].kind, "volume");
+        assert_eq!(api.depends_on["db"].condition, "service_healthy");
+        assert!(api.networks.contains_key("backend"));
+        assert_eq!(api.secrets[0].source, "token");
+        let limits = api.deploy.as_ref().unwrap().resources.as_ref().unwrap();
+        assert_eq!(limits.limits.as_ref().unwrap().cpus.as_deref(), Some("0.5"));
+
+        let db = &model.services["db"];
+        assert_eq!(db.environment["POSTGRES_PORT"].as_deref(), Some("5432"));
+        assert!(db.extra.contains_key("stop_grace_period"));
+        assert!(model.networks.contains_key("backend"));
+        assert!(model.extra.contains_key("x-common"));
+
+        // The long form written back parses into the same model
+        let reparsed: ComposeFile = model.to_yaml().unwrap().parse().unwrap();
+        assert_eq!(model, reparsed);
+    }
+}
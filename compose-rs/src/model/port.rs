@@ -0,0 +1,140 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::de;
+use crate::ComposeError;
+
+/// A port of a service in the long syntax, short syntax entries are converted on parse.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Port {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub target: u16,
+    /// The published host port or port range, e.g. `8080` or `8000-8010`
+    #[serde(
+        default,
+        deserialize_with = "de::string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub published: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+}
+
+impl Port {
+    /// Parse the short syntax `[[host_ip:]published:]target[/protocol]`.
+    ///
+    /// A target range like `3000-3005` results in one port per target.
+    pub fn parse_short(spec: &str) -> Result<Vec<Self>, ComposeError> {
+        let error = || ComposeError::ParseError(format!("Invalid port: {spec}"));
+
+        let (spec_ports, protocol) = match spec.rsplit_once('/') {
+            Some((ports, protocol)) => (ports, Some(protocol.to_string())),
+            None => (spec, None),
+        };
+
+        // IPv6 host addresses may be enclosed in brackets
+        let (host_ip, rest) = match spec_ports.strip_prefix('[') {
+            Some(rest) => {
+                let (ip, rest) = rest.split_once(']').ok_or_else(error)?;
+                (
+                    Some(ip.to_string()),
+                    rest.strip_prefix(':').ok_or_else(error)?,
+                )
+            }
+            None => (None, spec_ports),
+        };
+
+        let mut parts = rest.rsplitn(3, ':');
+        let target = parts.next().ok_or_else(error)?;
+        let published = parts.next().filter(|p| !p.is_empty());
+        let host_ip = match parts.next() {
+            Some(ip) if host_ip.is_none() => Some(ip.to_string()),
+            Some(_) => return Err(error()),
+            None => host_ip,
+        };
+
+        let targets = parse_range(target).ok_or_else(error)?;
+        let published = match published {
+            Some(published) => {
+                let range = parse_range(published).ok_or_else(error)?;
+                Some((published.to_string(), range))
+            }
+            None => None,
+        };
+
+        let ports = targets
+            .clone()
+            .enumerate()
+            .map(|(index, target)| {
+                let published = match &published {
+                    // A published range with the same length as the target range maps pairwise
+                    Some((_, range)) if targets.len() > 1 && range.len() == targets.len() => {
+                        Some((range.start() + index as u16).to_string())
+                    }
+                    Some((published, _)) => Some(published.clone()),
+                    None => None,
+                };
+
+                Self {
+                    target,
+                    published,
+                    host_ip: host_ip.clone(),
+                    protocol: protocol.clone(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        Ok(ports)
+    }
+}
+
+fn parse_range(range: &str) -> Option<std::ops::RangeInclusive<u16>> {
+    match range.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+            (start <= end).then_some(start..=end)
+        }
+        None => {
+            let port = range.parse().ok()?;
+            Some(port..=port)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PortEntry {
+    Number(u16),
+    Short(String),
+    Long(Port),
+}
+
+pub(crate) fn deserialize_ports<'de, D>(deserializer: D) -> Result<Vec<Port>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = Option::<Vec<PortEntry>>::deserialize(deserializer)?.unwrap_or_default();
+
+    let mut ports = Vec::new();
+    for entry in entries {
+        match entry {
+            PortEntry::Number(target) => ports.push(Port {
+                target,
+                ..Default::default()
+            }),
+            PortEntry::Short(spec) => {
+                ports.extend(Port::parse_short(&spec).map_err(de::parse_error)?)
+            }
+            PortEntry::Long(port) => ports.push(port),
+        }
+    }
+
+    Ok(ports)
+}
@@ -0,0 +1,459 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::{
+    de,
+    port::{self, Port},
+};
+use crate::ComposeError;
+
+/// A service definition of a compose file.
+///
+/// Keys that are not modelled explicitly are kept in [Service::extra].
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Service {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<Build>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Command>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entrypoint: Option<Command>,
+    #[serde(
+        default,
+        deserialize_with = "de::key_values",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub environment: IndexMap<String, Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "de::one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub env_file: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "port::deserialize_ports",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub ports: Vec<Port>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_expose",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub expose: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_volumes",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub volumes: Vec<ServiceVolume>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_depends_on",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub depends_on: IndexMap<String, Dependency>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_networks",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub networks: IndexMap<String, ServiceNetwork>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<Healthcheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy: Option<Deploy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::labels",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub labels: IndexMap<String, String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_references",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub secrets: Vec<FileReference>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_references",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub configs: Vec<FileReference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privileged: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+/// A command in shell form (`string`) or exec form (`list`).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Command {
+    Shell(String),
+    Exec(Vec<String>),
+}
+
+/// The build section of a service, the short syntax only sets the context.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Build {
+    pub context: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dockerfile: Option<String>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub args: IndexMap<String, Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+#[derive(Deserialize)]
+struct LongBuild {
+    #[serde(default)]
+    context: Option<String>,
+    dockerfile: Option<String>,
+    #[serde(default, deserialize_with = "de::key_values")]
+    args: IndexMap<String, Option<String>>,
+    target: Option<String>,
+    #[serde(flatten)]
+    extra: IndexMap<String, serde_yaml::Value>,
+}
+
+impl<'de> Deserialize<'de> for Build {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum BuildSyntax {
+            Short(String),
+            Long(LongBuild),
+        }
+
+        Ok(match BuildSyntax::deserialize(deserializer)? {
+            BuildSyntax::Short(context) => Build {
+                context,
+                ..Default::default()
+            },
+            BuildSyntax::Long(build) => Build {
+                context: build.context.unwrap_or_else(|| ".".to_string()),
+                dockerfile: build.dockerfile,
+                args: build.args,
+                target: build.target,
+                extra: build.extra,
+            },
+        })
+    }
+}
+
+/// A volume of a service in the long syntax, short syntax entries are converted on parse.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ServiceVolume {
+    /// `volume`, `bind`, `tmpfs`, `npipe` or `cluster`
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+impl ServiceVolume {
+    /// Parse the short syntax `[source:]target[:mode]`.
+    pub fn parse_short(spec: &str) -> Result<Self, ComposeError> {
+        let error = || ComposeError::ParseError(format!("Invalid volume: {spec}"));
+        let parts = spec.split(':').collect::<Vec<_>>();
+
+        let (source, target, mode) = match parts.as_slice() {
+            [target] => (None, *target, None),
+            [source, target] => (Some(*source), *target, None),
+            [source, target, mode] => (Some(*source), *target, Some(*mode)),
+            _ => return Err(error()),
+        };
+
+        if target.is_empty() {
+            return Err(error());
+        }
+
+        let volume = match source {
+            Some(source) => Self::new(source, target),
+            None => Self {
+                kind: "volume".to_string(),
+                target: target.to_string(),
+                ..Default::default()
+            },
+        };
+
+        Ok(Self {
+            read_only: mode.and_then(|mode| mode.split(',').any(|m| m == "ro").then_some(true)),
+            ..volume
+        })
+    }
+
+    /// A bind mount if `source` is a path, otherwise a named volume.
+    pub fn new(source: impl ToString, target: impl ToString) -> Self {
+        let source = source.to_string();
+        let is_path = source.starts_with('.') || source.starts_with('/') || source.starts_with('~');
+
+        Self {
+            kind: match is_path {
+                true => "bind".to_string(),
+                false => "volume".to_string(),
+            },
+            source: Some(source),
+            target: target.to_string(),
+            read_only: None,
+            extra: IndexMap::new(),
+        }
+    }
+}
+
+fn deserialize_volumes<'de, D>(deserializer: D) -> Result<Vec<ServiceVolume>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum VolumeEntry {
+        Short(String),
+        Long(ServiceVolume),
+    }
+
+    Option::<Vec<VolumeEntry>>::deserialize(deserializer)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| match entry {
+            VolumeEntry::Short(spec) => ServiceVolume::parse_short(&spec).map_err(de::parse_error),
+            VolumeEntry::Long(volume) => Ok(volume),
+        })
+        .collect()
+}
+
+fn deserialize_expose<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Entry(#[serde(deserialize_with = "de::string_or_number")] Option<String>);
+
+    Ok(Option::<Vec<Entry>>::deserialize(deserializer)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|entry| entry.0)
+        .collect())
+}
+
+/// A dependency of a service, the list syntax uses the `service_started` condition.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Dependency {
+    #[serde(default = "Dependency::default_condition")]
+    pub condition: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+}
+
+impl Dependency {
+    fn default_condition() -> String {
+        "service_started".to_string()
+    }
+}
+
+impl Default for Dependency {
+    fn default() -> Self {
+        Self {
+            condition: Self::default_condition(),
+            restart: None,
+            required: None,
+        }
+    }
+}
+
+fn deserialize_depends_on<'de, D>(deserializer: D) -> Result<IndexMap<String, Dependency>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DependsOn {
+        List(Vec<String>),
+        #[serde(deserialize_with = "de::nullable_map")]
+        Map(IndexMap<String, Dependency>),
+    }
+
+    Ok(match Option::<DependsOn>::deserialize(deserializer)? {
+        None => IndexMap::new(),
+        Some(DependsOn::List(services)) => services
+            .into_iter()
+            .map(|service| (service, Dependency::default()))
+            .collect(),
+        Some(DependsOn::Map(map)) => map,
+    })
+}
+
+/// The configuration of a service on a network.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ServiceNetwork {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv4_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6_address: Option<String>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+fn deserialize_networks<'de, D>(
+    deserializer: D,
+) -> Result<IndexMap<String, ServiceNetwork>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Networks {
+        List(Vec<String>),
+        #[serde(deserialize_with = "de::nullable_map")]
+        Map(IndexMap<String, ServiceNetwork>),
+    }
+
+    Ok(match Option::<Networks>::deserialize(deserializer)? {
+        None => IndexMap::new(),
+        Some(Networks::List(networks)) => networks
+            .into_iter()
+            .map(|network| (network, ServiceNetwork::default()))
+            .collect(),
+        Some(Networks::Map(map)) => map,
+    })
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Healthcheck {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test: Option<Command>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_period: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Deploy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<Resources>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Resources {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits: Option<ResourceLimits>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reservations: Option<ResourceLimits>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Fraction of CPUs, e.g. `0.5`
+    #[serde(
+        default,
+        deserialize_with = "de::string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cpus: Option<String>,
+    /// Memory as a byte value, e.g. `512M`
+    #[serde(
+        default,
+        deserialize_with = "de::string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub memory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pids: Option<i64>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, serde_yaml::Value>,
+}
+
+/// A secret or config granted to a service, the short syntax only sets the source.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FileReference {
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub uid: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de::string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub gid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
+fn deserialize_references<'de, D>(deserializer: D) -> Result<Vec<FileReference>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Reference {
+        Short(String),
+        Long(FileReference),
+    }
+
+    Ok(Option::<Vec<Reference>>::deserialize(deserializer)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|reference| match reference {
+            Reference::Short(source) => FileReference {
+                source,
+                ..Default::default()
+            },
+            Reference::Long(reference) => reference,
+        })
+        .collect())
+}
@@ -0,0 +1,449 @@
+//! Builders to generate compose files from Rust.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use compose_rs::{model::{ComposeSpec, HealthcheckSpec, ServiceSpec}, Compose, ComposeCommand};
+//!
+//! let spec = ComposeSpec::new().service(
+//!     ServiceSpec::new("db")
+//!         .image("postgres:16")
+//!         .env("POSTGRES_PASSWORD", "secret")
+//!         .ephemeral_port(5432)
+//!         .volume("data", "/var/lib/postgresql/data")
+//!         .healthcheck(HealthcheckSpec::cmd(["pg_isready"]).interval(Duration::from_secs(1))),
+//! );
+//!
+//! let compose = Compose::from_model(spec).unwrap();
+//! compose.up().exec().unwrap();
+//! ```
+
+use std::time::Duration;
+
+use super::{
+    Build, Command, ComposeFile, Dependency, Deploy, Healthcheck, Network, Port, ResourceLimits,
+    Resources, Service, ServiceNetwork, ServiceVolume, Volume,
+};
+use crate::ComposeError;
+
+/// Builder for a [ComposeFile].
+///
+/// Named volumes and networks used by the services are declared at the top level
+/// when the file is built, unless they are declared explicitly.
+#[derive(Debug, Clone, Default)]
+pub struct ComposeSpec {
+    file: ComposeFile,
+}
+
+impl ComposeSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the top-level project name.
+    pub fn name(mut self, name: impl ToString) -> Self {
+        self.file.name = Some(name.to_string());
+        self
+    }
+
+    /// Add a service, a service with the same name is replaced.
+    pub fn service(mut self, service: ServiceSpec) -> Self {
+        let (name, service) = service.build();
+        self.file.services.insert(name, service);
+        self
+    }
+
+    /// Declare a named volume.
+    pub fn volume(mut self, name: impl ToString, volume: Volume) -> Self {
+        self.file.volumes.insert(name.to_string(), volume);
+        self
+    }
+
+    /// Declare a network.
+    pub fn network(mut self, name: impl ToString, network: Network) -> Self {
+        self.file.networks.insert(name.to_string(), network);
+        self
+    }
+
+    /// Build the compose file.
+    pub fn build(self) -> ComposeFile {
+        let mut file = self.file;
+
+        let volumes = file
+            .services
+            .values()
+            .flat_map(|service| &service.volumes)
+            .filter(|volume| volume.kind == "volume")
+            .filter_map(|volume| volume.source.clone())
+            .collect::<Vec<_>>();
+        for volume in volumes {
+            file.volumes.entry(volume).or_default();
+        }
+
+        let networks = file
+            .services
+            .values()
+            .flat_map(|service| service.networks.keys())
+            .filter(|network| *network != "default")
+            .cloned()
+            .collect::<Vec<_>>();
+        for network in networks {
+            file.networks.entry(network).or_default();
+        }
+
+        file
+    }
+
+    /// Build the compose file and serialize it to YAML.
+    pub fn to_yaml(self) -> Result<String, ComposeError> {
+        self.build().to_yaml()
+    }
+}
+
+impl From<ComposeSpec> for ComposeFile {
+    fn from(spec: ComposeSpec) -> Self {
+        spec.build()
+    }
+}
+
+/// Builder for a [Service].
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    name: String,
+    service: Service,
+}
+
+impl ServiceSpec {
+    pub fn new(name: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            service: Service::default(),
+        }
+    }
+
+    pub fn image(mut self, image: impl ToString) -> Self {
+        self.service.image = Some(image.to_string());
+        self
+    }
+
+    /// Build the image from the `context` directory.
+    pub fn build_context(mut self, context: impl ToString) -> Self {
+        self.service.build = Some(Build {
+            context: context.to_string(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Override the command of the image, in exec form.
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        self.service.command = Some(exec_form(command));
+        self
+    }
+
+    /// Override the entrypoint of the image, in exec form.
+    pub fn entrypoint<I, S>(mut self, entrypoint: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        self.service.entrypoint = Some(exec_form(entrypoint));
+        self
+    }
+
+    pub fn container_name(mut self, name: impl ToString) -> Self {
+        self.service.container_name = Some(name.to_string());
+        self
+    }
+
+    /// Set an environment variable.
+    pub fn env(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.service
+            .environment
+            .insert(key.to_string(), Some(value.to_string()));
+        self
+    }
+
+    /// Pass an environment variable through from the environment of compose.
+    pub fn env_passthrough(mut self, key: impl ToString) -> Self {
+        self.service.environment.insert(key.to_string(), None);
+        self
+    }
+
+    pub fn env_file(mut self, path: impl ToString) -> Self {
+        self.service.env_file.push(path.to_string());
+        self
+    }
+
+    /// Publish the container port `target` on the host port `published`.
+    pub fn port(self, published: u16, target: u16) -> Self {
+        self.port_spec(Port {
+            target,
+            published: Some(published.to_string()),
+            ..Default::default()
+        })
+    }
+
+    /// Publish the container port `target` on a random host port.
+    ///
+    /// The assigned port can be looked up with [Compose::port](crate::Compose::port),
+    /// which allows multiple stacks to run side by side.
+    pub fn ephemeral_port(self, target: u16) -> Self {
+        self.port_spec(Port {
+            target,
+            ..Default::default()
+        })
+    }
+
+    pub fn port_spec(mut self, port: Port) -> Self {
+        self.service.ports.push(port);
+        self
+    }
+
+    /// Mount `source` at `target`, `source` is a bind mount if it is a path and a named
+    /// volume otherwise.
+    pub fn volume(self, source: impl ToString, target: impl ToString) -> Self {
+        self.volume_spec(ServiceVolume::new(source, target))
+    }
+
+    pub fn volume_spec(mut self, volume: ServiceVolume) -> Self {
+        self.service.volumes.push(volume);
+        self
+    }
+
+    /// Start after `service` has started.
+    pub fn depends_on(self, service: impl ToString) -> Self {
+        self.depends_on_condition(service, "service_started")
+    }
+
+    /// Start after `service` has passed its healthcheck.
+    pub fn depends_on_healthy(self, service: impl ToString) -> Self {
+        self.depends_on_condition(service, "service_healthy")
+    }
+
+    /// Start after `service` has run to successful completion.
+    pub fn depends_on_completed(self, service: impl ToString) -> Self {
+        self.depends_on_condition(service, "service_completed_successfully")
+    }
+
+    fn depends_on_condition(mut self, service: impl ToString, condition: &str) -> Self {
+        self.service.depends_on.insert(
+            service.to_string(),
+            Dependency {
+                condition: condition.to_string(),
+                ..Default::default()
+            },
+        );
+        self
+    }
+
+    pub fn healthcheck(mut self, healthcheck: HealthcheckSpec) -> Self {
+        self.service.healthcheck = Some(healthcheck.healthcheck);
+        self
+    }
+
+    pub fn network(mut self, network: impl ToString) -> Self {
+        self.service
+            .networks
+            .insert(network.to_string(), ServiceNetwork::default());
+        self
+    }
+
+    pub fn label(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.service
+            .labels
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// The restart policy, e.g. `always` or `on-failure`.
+    pub fn restart(mut self, policy: impl ToString) -> Self {
+        self.service.restart = Some(policy.to_string());
+        self
+    }
+
+    pub fn replicas(mut self, replicas: u32) -> Self {
+        self.deploy().replicas = Some(replicas);
+        self
+    }
+
+    /// Limit the service to a fraction of CPUs, e.g. `0.5`.
+    pub fn cpus(mut self, cpus: f64) -> Self {
+        self.limits().cpus = Some(cpus.to_string());
+        self
+    }
+
+    /// Limit the memory of the service, e.g. `512M`.
+    pub fn memory(mut self, memory: impl ToString) -> Self {
+        self.limits().memory = Some(memory.to_string());
+        self
+    }
+
+    pub fn profile(mut self, profile: impl ToString) -> Self {
+        self.service.profiles.push(profile.to_string());
+        self
+    }
+
+    /// Set a key that is not covered by the builder.
+    pub fn extra(mut self, key: impl ToString, value: impl Into<serde_yaml::Value>) -> Self {
+        self.service.extra.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// The name and definition of the service.
+    pub fn build(self) -> (String, Service) {
+        (self.name, self.service)
+    }
+
+    fn deploy(&mut self) -> &mut Deploy {
+        self.service.deploy.get_or_insert_with(Deploy::default)
+    }
+
+    fn limits(&mut self) -> &mut ResourceLimits {
+        self.deploy()
+            .resources
+            .get_or_insert_with(Resources::default)
+            .limits
+            .get_or_insert_with(ResourceLimits::default)
+    }
+}
+
+/// Builder for a [Healthcheck].
+#[derive(Debug, Clone)]
+pub struct HealthcheckSpec {
+    healthcheck: Healthcheck,
+}
+
+impl HealthcheckSpec {
+    /// Run `command` in exec form, `["CMD", ...]`.
+    pub fn cmd<I, S>(command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        let test = std::iter::once("CMD".to_string())
+            .chain(command.into_iter().map(|arg| arg.to_string()))
+            .collect();
+
+        Self::test(Command::Exec(test))
+    }
+
+    /// Run `command` with the shell of the container, `["CMD-SHELL", command]`.
+    pub fn shell(command: impl ToString) -> Self {
+        Self::test(Command::Exec(vec![
+            "CMD-SHELL".to_string(),
+            command.to_string(),
+        ]))
+    }
+
+    fn test(test: Command) -> Self {
+        Self {
+            healthcheck: Healthcheck {
+                test: Some(test),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.healthcheck.interval = Some(format_duration(interval));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.healthcheck.timeout = Some(format_duration(timeout));
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.healthcheck.retries = Some(retries);
+        self
+    }
+
+    pub fn start_period(mut self, start_period: Duration) -> Self {
+        self.healthcheck.start_period = Some(format_duration(start_period));
+        self
+    }
+
+    pub fn start_interval(mut self, start_interval: Duration) -> Self {
+        self.healthcheck.start_interval = Some(format_duration(start_interval));
+        self
+    }
+}
+
+fn exec_form<I, S>(command: I) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: ToString,
+{
+    Command::Exec(command.into_iter().map(|arg| arg.to_string()).collect())
+}
+
+/// Format a duration in the syntax of compose, e.g. `5s` or `1500ms`.
+fn format_duration(duration: Duration) -> String {
+    match duration.subsec_millis() {
+        0 => format!("{}s", duration.as_secs()),
+        _ => format!("{}ms", duration.as_millis()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_compose_file() {
+        let file = ComposeSpec::new()
+            .name("stack")
+            .service(
+                ServiceSpec::new("db")
+                    .image("postgres:16")
+                    .env("POSTGRES_PASSWORD", "secret")
+                    .ephemeral_port(5432)
+                    .volume("data", "/var/lib/postgresql/data")
+                    .network("backend")
+                    .healthcheck(
+                        HealthcheckSpec::cmd(["pg_isready"])
+                            .interval(Duration::from_millis(500))
+                            .retries(10),
+                    ),
+            )
+            .service(
+                ServiceSpec::new("api")
+                    .build_context("./api")
+                    .command(["serve", "--port", "8080"])
+                    .port(8080, 8080)
+                    .volume("./config", "/etc/api")
+                    .depends_on_healthy("db")
+                    .memory("512M")
+                    .replicas(2),
+            )
+            .build();
+
+        assert!(file.volumes.contains_key("data"));
+        assert!(file.networks.contains_key("backend"));
+        assert_eq!(file.volumes.len(), 1);
+
+        let yaml = file.to_yaml().unwrap();
+        let reparsed: ComposeFile = yaml.parse().unwrap();
+        assert_eq!(file, reparsed);
+
+        let db = &reparsed.services["db"];
+        let healthcheck = db.healthcheck.as_ref().unwrap();
+        assert_eq!(healthcheck.interval.as_deref(), Some("500ms"));
+        assert_eq!(
+            healthcheck.test,
+            Some(Command::Exec(vec!["CMD".into(), "pg_isready".into()]))
+        );
+        assert_eq!(db.ports[0].published, None);
+
+        let api = &reparsed.services["api"];
+        assert_eq!(api.volumes[0].kind, "bind");
+        assert_eq!(api.depends_on["db"].condition, "service_healthy");
+        assert_eq!(api.deploy.as_ref().unwrap().replicas, Some(2));
+    }
+}
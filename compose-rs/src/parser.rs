@@ -1,64 +1,130 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
 use crate::{
-    container::{Container, ContainerStatus, PortMapping},
+    container::{Container, ContainerStatus, PortMapping, Status},
     ComposeError,
 };
 
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PsEntry {
+    name: String,
+    image: String,
+    command: String,
+    service: String,
+    /// Unix timestamp in seconds
+    #[serde(default)]
+    created: Option<i64>,
+    /// e.g. `2 minutes ago`
+    #[serde(default)]
+    running_for: String,
+    state: String,
+    status: String,
+    #[serde(default)]
+    exit_code: Option<i64>,
+    #[serde(default)]
+    ports: String,
+}
+
+/// Parse the output of `ps --format json`, which is a JSON array in older compose
+/// versions and one JSON object per line in newer ones.
 pub(crate) fn parse_ps(output: &str) -> Result<Vec<Container>, ComposeError> {
-    let mut lines = output.lines();
-
-    let header = lines
-        .next()
-        .ok_or(ComposeError::ParseError("Missing header".to_string()))?;
-
-    let header_indices = find_header_indices(header);
-    let header_map: std::collections::HashMap<&str, usize> = header
-        .split_whitespace()
-        .enumerate()
-        .map(|(i, h)| (h, i))
-        .collect();
-
-    let result = lines
-        .map(|line| {
-            let parts: Vec<&str> = header_indices
-                .iter()
-                .enumerate()
-                .map(|(index, (_header_name, header_index))| {
-                    // These *should* be safe to unwrap because we're guaranteed to have a match (?)
-                    if let Some(next_index) = header_indices.get(index + 1) {
-                        line.get(*header_index..next_index.1).unwrap().trim()
-                    } else {
-                        line.get(*header_index..).unwrap().trim()
-                    }
-                })
-                .collect();
+    let output = output.trim();
+    let entries: Vec<PsEntry> = if output.starts_with('[') {
+        serde_json::from_str(output)?
+    } else {
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let created = match entry.created {
+                Some(seconds) if seconds > 0 => UNIX_EPOCH + Duration::from_secs(seconds as u64),
+                _ => {
+                    let age = parse_relative_duration(&entry.running_for).unwrap_or_default();
+                    SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH)
+                }
+            };
+
+            let mut status = ContainerStatus::from_string(&entry.status);
+            // The status column does not tell paused and running containers apart reliably
+            if !entry.state.is_empty() && status.status != Status::Paused {
+                status.status = Status::from_string(&entry.state);
+            }
+            if status.status == Status::Exited && status.exit_code.is_none() {
+                status.exit_code = entry.exit_code.and_then(|code| u8::try_from(code).ok());
+            }
+
+            let mut ports = Vec::new();
+            let mut warnings = Vec::new();
+            for port in entry.ports.split(", ").filter(|p| !p.is_empty()) {
+                match PortMapping::from_string(port) {
+                    Ok(mappings) => ports.extend(mappings),
+                    Err(err) => warnings.push(err.to_string()),
+                }
+            }
 
             Container {
-                name: parts[header_map["NAME"]].to_string(),
-                image: parts[header_map["IMAGE"]].to_string(),
-                command: parts[header_map["COMMAND"]].trim_matches('"').to_string(),
-                service: parts[header_map["SERVICE"]].to_string(),
-                created: parts[header_map["CREATED"]].to_string(),
-                status: ContainerStatus::from_string(parts[header_map["STATUS"]]),
-                ports: parts[header_map["PORTS"]]
-                    .split(", ")
-                    .filter(|p| !p.is_empty())
-                    .flat_map(|p| PortMapping::from_string(p).unwrap_or_default())
-                    .collect(),
+                name: entry.name,
+                image: entry.image,
+                command: entry.command.trim_matches('"').to_string(),
+                service: entry.service,
+                created,
+                status,
+                ports,
+                warnings,
             }
         })
-        .collect();
-
-    Ok(result)
+        .collect())
 }
 
-fn find_header_indices(header_line: &str) -> Vec<(&str, usize)> {
-    let headers: Vec<&str> = header_line.split_whitespace().collect();
+/// Parse a relative duration the way docker prints it, e.g. `2 minutes`,
+/// `About an hour ago` or `Less than a second`.
+///
+/// Months and years are approximated as 30 and 365 days.
+pub(crate) fn parse_relative_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    let input = input.strip_suffix(" ago").unwrap_or(&input).trim();
 
-    headers
-        .iter()
-        // We can unwrap here because we know that the header exists in the line
-        .map(|h| (*h, header_line.find(h).unwrap()))
-        .collect()
+    if input.is_empty() {
+        return None;
+    }
+    if input == "less than a second" {
+        return Some(Duration::ZERO);
+    }
+
+    let (count, unit) = input.split_once(' ')?;
+    let count = match count {
+        "about" | "a" | "an" => {
+            let unit = unit.trim_start_matches("an ").trim_start_matches("a ");
+            return unit_seconds(unit).map(Duration::from_secs);
+        }
+        count => count.parse::<u64>().ok()?,
+    };
+
+    let seconds = unit_seconds(unit)?;
+    Some(Duration::from_secs(count.checked_mul(seconds)?))
+}
+
+fn unit_seconds(unit: &str) -> Option<u64> {
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 3600,
+        "day" => 86400,
+        "week" => 7 * 86400,
+        "month" => 30 * 86400,
+        "year" => 365 * 86400,
+        _ => return None,
+    };
+    Some(seconds)
 }
 
 pub(crate) fn remove_ansi_codes(input: &str) -> Result<String, ComposeError> {
@@ -67,3 +133,155 @@ pub(crate) fn remove_ansi_codes(input: &str) -> Result<String, ComposeError> {
 
     Ok(re.replace_all(input, "").to_string())
 }
+
+/// Parse an RFC 3339 timestamp like `2024-04-20T14:59:44.123456789+02:00`.
+///
+/// A space is accepted in place of the `T` separator.
+pub(crate) fn parse_rfc3339(input: &str) -> Result<SystemTime, ComposeError> {
+    let error = || ComposeError::ParseError(format!("Invalid timestamp: {input}"));
+    let number = |s: &str| s.parse::<i64>().map_err(|_| error());
+
+    let input = input.trim();
+    if input.len() < 19 || !input.is_char_boundary(19) {
+        return Err(error());
+    }
+
+    let (datetime, rest) = input.split_at(19);
+    let bytes = datetime.as_bytes();
+    if bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return Err(error());
+    }
+
+    let year = number(&datetime[0..4])?;
+    let month = number(&datetime[5..7])?;
+    let day = number(&datetime[8..10])?;
+    let hour = number(&datetime[11..13])?;
+    let minute = number(&datetime[14..16])?;
+    let second = number(&datetime[17..19])?;
+
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return Err(error());
+    }
+
+    // Fractional seconds
+    let (nanos, offset) = match rest.strip_prefix('.') {
+        Some(rest) => {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (fraction, offset) = rest.split_at(digits);
+            if fraction.is_empty() {
+                return Err(error());
+            }
+
+            let mut nanos = 0;
+            for (i, digit) in fraction.bytes().take(9).enumerate() {
+                nanos += u32::from(digit - b'0') * 10u32.pow(8 - i as u32);
+            }
+            (nanos, offset)
+        }
+        None => (0, rest),
+    };
+
+    let offset = match offset {
+        "Z" | "z" => 0,
+        offset if offset.len() == 6 && offset.as_bytes()[3] == b':' => {
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(error()),
+            };
+            sign * (number(&offset[1..3])? * 3600 + number(&offset[4..6])? * 60)
+        }
+        _ => return Err(error()),
+    };
+
+    let seconds =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+
+    let time = match seconds >= 0 {
+        true => UNIX_EPOCH + Duration::from_secs(seconds as u64),
+        false => UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+    };
+
+    Ok(time + Duration::from_nanos(u64::from(nanos)))
+}
+
+/// Days since the unix epoch for a date in the proleptic gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = (month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ps() {
+        let output = r#"{"Command":"\"docker-entrypoint.sh\"","Created":1713617980,"ExitCode":0,"Health":"","ID":"9ca40acb565a","Image":"rqlite/rqlite:8.22.2","Name":"examples-rqlite-1","Ports":"0.0.0.0:32768->4001/tcp, 4002/tcp","Project":"examples","RunningFor":"2 minutes ago","Service":"rqlite","State":"running","Status":"Up 2 minutes"}
+{"Command":"\"sh\"","Created":0,"ExitCode":3,"Image":"busybox","Name":"examples-job-1","Ports":"","RunningFor":"About an hour ago","Service":"job","State":"exited","Status":"Exited (3) 5 seconds ago"}
+"#;
+
+        let containers = parse_ps(output).unwrap();
+        assert_eq!(containers.len(), 2);
+
+        let rqlite = &containers[0];
+        assert_eq!(rqlite.command, "docker-entrypoint.sh");
+        assert_eq!(
+            rqlite.created,
+            UNIX_EPOCH + Duration::from_secs(1_713_617_980)
+        );
+        assert_eq!(rqlite.uptime(), Some(Duration::from_secs(120)));
+        assert_eq!(rqlite.ports.len(), 2);
+        assert!(rqlite.warnings.is_empty());
+
+        let job = &containers[1];
+        assert_eq!(job.status.status, Status::Exited);
+        assert_eq!(job.status.exit_code, Some(3));
+        assert_eq!(job.uptime(), None);
+        let age = SystemTime::now().duration_since(job.created).unwrap();
+        assert!(age >= Duration::from_secs(3600) && age < Duration::from_secs(3660));
+
+        // Older compose versions print a JSON array
+        let array = format!("[{}]", output.lines().next().unwrap());
+        assert_eq!(parse_ps(&array).unwrap().len(), 1);
+
+        // An age too large for the system time is clamped rather than overflowing
+        let ancient = output
+            .lines()
+            .nth(1)
+            .unwrap()
+            .replace("About an hour ago", "500000000000 years ago");
+        assert_eq!(parse_ps(&ancient).unwrap()[0].created, UNIX_EPOCH);
+    }
+
+    #[test]
+    fn test_parse_relative_duration() {
+        let parse = parse_relative_duration;
+        assert_eq!(parse("Less than a second"), Some(Duration::ZERO));
+        assert_eq!(parse("1 second ago"), Some(Duration::from_secs(1)));
+        assert_eq!(parse("About a minute"), Some(Duration::from_secs(60)));
+        assert_eq!(parse("3 hours"), Some(Duration::from_secs(3 * 3600)));
+        assert_eq!(parse("2 weeks ago"), Some(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse("18446744073709551615 years"), None);
+        assert_eq!(parse("Created"), None);
+        assert_eq!(parse(""), None);
+    }
+}
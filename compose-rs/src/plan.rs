@@ -0,0 +1,565 @@
+//! Declarative reconciliation of a compose project, in two steps.
+//!
+//! [Compose::plan] compares a desired configuration with the containers of the project
+//! and lists the actions needed to reach it, each with a reason. [Compose::apply] runs
+//! them with [UpCommand](crate::command::UpCommand),
+//! [ScaleCommand](crate::command::ScaleCommand) and
+//! [DownCommand](crate::command::DownCommand).
+//!
+//! ```rust,no_run
+//! use compose_rs::{Compose, ComposeCommand};
+//!
+//! let compose = Compose::builder().path("docker-compose.yml").build().unwrap();
+//!
+//! let mut desired = compose.config().exec().unwrap();
+//! desired.services["api"].scale = Some(3);
+//!
+//! let plan = compose.plan(desired).unwrap();
+//! print!("{plan}");
+//! compose.apply(&plan).dry_run().exec().unwrap();
+//! ```
+
+use std::{fmt, time::Instant};
+
+use crate::{
+    command::PullPolicy,
+    drift::{image_id, project_containers, ObservedContainer},
+    graph::DependencyGraph,
+    model::{ComposeFile, Service},
+    Compose, ComposeCommand, ComposeError,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Pull the image of the service
+    Pull { service: String, image: String },
+    /// Create the containers of a service that has none
+    Create { service: String, replicas: u32 },
+    /// Recreate the containers of the service
+    Recreate { service: String, replicas: u32 },
+    /// Change the number of running containers
+    Scale { service: String, from: u32, to: u32 },
+    /// Stop and remove the containers of a service scaled to zero
+    Stop { service: String },
+    /// Remove the containers of a service that is not declared
+    Remove {
+        service: String,
+        containers: Vec<String>,
+    },
+}
+
+impl Action {
+    pub fn service(&self) -> &str {
+        match self {
+            Action::Pull { service, .. }
+            | Action::Create { service, .. }
+            | Action::Recreate { service, .. }
+            | Action::Scale { service, .. }
+            | Action::Stop { service }
+            | Action::Remove { service, .. } => service,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Pull { service, image } => write!(f, "pull {service} ({image})"),
+            Action::Create { service, replicas } => {
+                write!(f, "+ create {service} ({replicas} replicas)")
+            }
+            Action::Recreate { service, replicas } => {
+                write!(f, "~ recreate {service} ({replicas} replicas)")
+            }
+            Action::Scale { service, from, to } => write!(f, "~ scale {service} {from} -> {to}"),
+            Action::Stop { service } => write!(f, "- stop {service}"),
+            Action::Remove {
+                service,
+                containers,
+            } => write!(f, "- remove {service} ({})", containers.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedAction {
+    pub action: Action,
+    pub reason: String,
+}
+
+/// The actions to reach a desired configuration, in the order they are applied.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    actions: Vec<PlannedAction>,
+    desired: ComposeFile,
+    project: String,
+}
+
+impl Plan {
+    pub fn actions(&self) -> &[PlannedAction] {
+        &self.actions
+    }
+
+    /// Whether the project already matches the desired configuration.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// The configuration the plan reconciles to.
+    pub fn desired(&self) -> &ComposeFile {
+        &self.desired
+    }
+
+    /// `normalized` is the desired configuration as resolved by compose, it is compared
+    /// with the `current` one so that defaults and short syntax don't count as changes.
+    fn build(
+        desired: ComposeFile,
+        normalized: &ComposeFile,
+        current: &ComposeFile,
+        hashes: &indexmap::IndexMap<String, String>,
+        observed: &[ObservedContainer],
+        image_ids: &dyn Fn(&str) -> Option<String>,
+        project: String,
+    ) -> Result<Self, ComposeError> {
+        let order = DependencyGraph::from_model(&desired).start_order()?;
+
+        let mut pulls = Vec::new();
+        let mut changes = Vec::new();
+
+        for name in &order {
+            let service = &desired.services[name];
+            let replicas = replicas(service);
+            let containers = observed
+                .iter()
+                .filter(|c| c.service == *name)
+                .collect::<Vec<_>>();
+            let running = containers.iter().filter(|c| c.running).count() as u32;
+
+            // Images that are built are not pulled
+            let image = service.image.as_ref().filter(|_| service.build.is_none());
+            let local_image = image.and_then(|image| image_ids(image));
+
+            let pull = match (image, &local_image) {
+                (Some(image), None) if replicas > 0 => {
+                    pulls.push(PlannedAction {
+                        action: Action::Pull {
+                            service: name.clone(),
+                            image: image.clone(),
+                        },
+                        reason: format!("image {image} is not present locally"),
+                    });
+                    true
+                }
+                _ => false,
+            };
+
+            if replicas == 0 {
+                if running > 0 {
+                    changes.push(PlannedAction {
+                        action: Action::Stop {
+                            service: name.clone(),
+                        },
+                        reason: format!("{running} running, 0 desired"),
+                    });
+                }
+                continue;
+            }
+
+            if containers.is_empty() {
+                changes.push(PlannedAction {
+                    action: Action::Create {
+                        service: name.clone(),
+                        replicas,
+                    },
+                    reason: "service has no containers".to_string(),
+                });
+                continue;
+            }
+
+            let stale_image = containers.iter().find(
+                |c| matches!((&local_image, &c.image_id), (Some(local), Some(id)) if local != id),
+            );
+            let stale_config = containers.iter().find(|c| {
+                matches!((hashes.get(name), &c.config_hash), (Some(hash), Some(actual)) if hash != actual)
+            });
+
+            let changed = match (current.services.get(name), normalized.services.get(name)) {
+                (Some(current), Some(normalized)) => !same_configuration(current, normalized),
+                _ => true,
+            };
+            let reason = if changed {
+                Some("configuration changed".to_string())
+            } else if pull {
+                Some(format!(
+                    "image {} is pulled",
+                    image.cloned().unwrap_or_default()
+                ))
+            } else if let Some(container) = stale_image {
+                Some(format!(
+                    "container {} runs an outdated image of {}",
+                    container.name,
+                    image.cloned().unwrap_or_default()
+                ))
+            } else {
+                stale_config.map(|container| {
+                    format!(
+                        "container {} was created from a different configuration",
+                        container.name
+                    )
+                })
+            };
+
+            match reason {
+                Some(reason) => changes.push(PlannedAction {
+                    action: Action::Recreate {
+                        service: name.clone(),
+                        replicas,
+                    },
+                    reason,
+                }),
+                None if running != replicas => changes.push(PlannedAction {
+                    action: Action::Scale {
+                        service: name.clone(),
+                        from: running,
+                        to: replicas,
+                    },
+                    reason: format!("{running} running, {replicas} desired"),
+                }),
+                None => {}
+            }
+        }
+
+        let mut orphans: Vec<(String, Vec<String>)> = Vec::new();
+        for container in observed {
+            if desired.services.contains_key(&container.service) {
+                continue;
+            }
+            match orphans.iter_mut().find(|(s, _)| *s == container.service) {
+                Some((_, containers)) => containers.push(container.name.clone()),
+                None => orphans.push((container.service.clone(), vec![container.name.clone()])),
+            }
+        }
+        let removals = orphans
+            .into_iter()
+            .map(|(service, containers)| PlannedAction {
+                action: Action::Remove {
+                    service,
+                    containers,
+                },
+                reason: "service is not declared".to_string(),
+            });
+
+        let mut actions = pulls;
+        actions.extend(changes);
+        actions.extend(removals);
+
+        Ok(Self {
+            actions,
+            desired,
+            project,
+        })
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.actions.is_empty() {
+            return writeln!(f, "No changes, {} is up to date.", self.project);
+        }
+
+        for planned in &self.actions {
+            writeln!(f, "{}: {}", planned.action, planned.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether two services only differ in their number of replicas.
+fn same_configuration(a: &Service, b: &Service) -> bool {
+    let without_replicas = |service: &Service| {
+        let mut service = service.clone();
+        service.scale = None;
+        if let Some(deploy) = &mut service.deploy {
+            deploy.replicas = None;
+        }
+        service
+    };
+
+    without_replicas(a) == without_replicas(b)
+}
+
+fn replicas(service: &Service) -> u32 {
+    service
+        .scale
+        .or_else(|| service.deploy.as_ref().and_then(|d| d.replicas))
+        .unwrap_or(1)
+}
+
+/// Applies a [Plan], created by [Compose::apply].
+pub struct Apply<'a> {
+    compose: &'a Compose,
+    plan: &'a Plan,
+    dry_run: bool,
+}
+
+impl Apply<'_> {
+    /// Pass `--dry-run` to compose, which prints what each step would do.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Run the actions of the plan in order.
+    pub fn exec(self) -> Result<(), ComposeError> {
+        if self.plan.is_empty() {
+            return Ok(());
+        }
+
+        let compose = self
+            .compose
+            .with_model(self.plan.desired.clone(), &self.plan.project)?;
+
+        let pulls = self
+            .plan
+            .actions
+            .iter()
+            .filter(|planned| matches!(planned.action, Action::Pull { .. }))
+            .map(|planned| planned.action.service())
+            .collect::<Vec<_>>();
+
+        for planned in &self.plan.actions {
+            match &planned.action {
+                Action::Pull { .. } => {}
+                Action::Create { service, replicas } | Action::Recreate { service, replicas } => {
+                    let mut up = compose.up().no_deps().scale(service, *replicas);
+                    if pulls.contains(&service.as_str()) {
+                        up = up.pull(PullPolicy::Always);
+                    }
+                    if matches!(planned.action, Action::Recreate { .. }) {
+                        up = up.force_recreate();
+                    }
+                    if self.dry_run {
+                        up = up.dry_run();
+                    }
+                    up.service(service).exec()?;
+                }
+                Action::Scale { service, to, .. } => {
+                    let mut scale = compose.scale().no_deps().service(*to, service);
+                    if self.dry_run {
+                        scale = scale.dry_run();
+                    }
+                    scale.exec()?;
+                }
+                Action::Stop { service } => {
+                    let mut scale = compose.scale().no_deps().service(0, service);
+                    if self.dry_run {
+                        scale = scale.dry_run();
+                    }
+                    scale.exec()?;
+                }
+                Action::Remove { .. } => {}
+            }
+        }
+
+        if self
+            .plan
+            .actions
+            .iter()
+            .any(|planned| matches!(planned.action, Action::Remove { .. }))
+        {
+            // Orphans are removed by compose itself, without a service to start the
+            // whole project is taken down
+            if self.plan.desired.services.is_empty() {
+                let mut down = compose.down().remove_orphans();
+                if self.dry_run {
+                    down = down.dry_run();
+                }
+                down.exec()?;
+            } else {
+                let mut up = compose.up().no_recreate().remove_orphans();
+                if self.dry_run {
+                    up = up.dry_run();
+                }
+                up.exec()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Compose {
+    /// Run compose with another configuration for the same project, relative paths
+    /// still resolve against the directory of the original files.
+    fn with_model(&self, model: ComposeFile, project: &str) -> Result<Compose, ComposeError> {
+        let mut builder = Compose::builder()
+            .model(model)
+            .project_name(project)
+            .project_directory(self.project_dir().to_string_lossy());
+        if let Some(policy) = &self.retry {
+            builder = builder.retry(policy.clone());
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Plan the actions to reach the desired configuration, e.g. an edited
+    /// [config](Compose::config) model, from the current containers of the project.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ComposeError::DependencyCycle] if the desired services depend on each
+    /// other in a cycle.
+    pub fn plan(&self, desired: impl Into<ComposeFile>) -> Result<Plan, ComposeError> {
+        let desired = desired.into();
+        let current = self.config().exec()?;
+        let hashes = self.config().hashes(&[])?;
+
+        let project = self
+            .project_name()
+            .or(desired.name.as_deref())
+            .or(current.name.as_deref())
+            .ok_or_else(|| {
+                ComposeError::ParseError("Resolved configuration has no project name".to_string())
+            })?
+            .to_string();
+
+        // Resolve the desired configuration like the current one, so both are compared
+        // with the same defaults and normalization
+        let normalized = self
+            .with_model(desired.clone(), &project)?
+            .config()
+            .exec()?;
+
+        let options = self.exec_options().step(Instant::now());
+        let observed = project_containers(&project, &options)?;
+        let image_ids = |image: &str| image_id(image, &options);
+
+        Plan::build(
+            desired,
+            &normalized,
+            &current,
+            &hashes,
+            &observed,
+            &image_ids,
+            project,
+        )
+    }
+
+    /// Apply a plan, see [Apply::dry_run] to only print the steps.
+    pub fn apply<'a>(&'a self, plan: &'a Plan) -> Apply<'a> {
+        Apply {
+            compose: self,
+            plan,
+            dry_run: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::*;
+
+    fn container(name: &str, service: &str, image_id: &str, hash: &str) -> ObservedContainer {
+        ObservedContainer {
+            name: name.to_string(),
+            service: service.to_string(),
+            running: true,
+            config_hash: Some(hash.to_string()),
+            image_id: Some(image_id.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let current: ComposeFile = "services:
+  api:
+    image: api:1
+    depends_on: [db]
+  db:
+    image: postgres:16
+  cache:
+    image: redis:7
+  web:
+    image: nginx:1
+    ports:
+      - target: 80
+        published: \"8080\"
+        protocol: tcp
+        mode: ingress
+  queue:
+    image: rabbitmq:3
+"
+        .parse()
+        .unwrap();
+
+        let mut desired = current.clone();
+        desired.services["api"].scale = Some(3);
+        desired.services["db"].image = Some("postgres:17".to_string());
+        desired.services["cache"].scale = Some(0);
+        let normalized = desired.clone();
+
+        // Short syntax as written in a file, compose resolves it to the long syntax above
+        let short: ComposeFile = "services:\n  web:\n    ports: [\"8080:80\"]\n"
+            .parse()
+            .unwrap();
+        desired.services["web"].ports = short.services["web"].ports.clone();
+        assert_ne!(desired.services["web"], current.services["web"]);
+
+        let hashes = ["api", "db", "cache", "web", "queue"]
+            .into_iter()
+            .map(|s| (s.to_string(), "hash".to_string()))
+            .collect::<IndexMap<_, _>>();
+
+        let observed = [
+            container("app-api-1", "api", "sha256:api1", "hash"),
+            container("app-db-1", "db", "sha256:pg16", "hash"),
+            container("app-cache-1", "cache", "sha256:redis", "hash"),
+            container("app-web-1", "web", "sha256:nginx-old", "hash"),
+            container("app-worker-1", "worker", "sha256:worker", "hash"),
+        ];
+
+        let image_ids = |image: &str| match image {
+            "api:1" => Some("sha256:api1".to_string()),
+            "redis:7" => Some("sha256:redis".to_string()),
+            "nginx:1" => Some("sha256:nginx".to_string()),
+            "rabbitmq:3" => Some("sha256:rabbit".to_string()),
+            _ => None,
+        };
+
+        let plan = Plan::build(
+            desired,
+            &normalized,
+            &current,
+            &hashes,
+            &observed,
+            &image_ids,
+            "app".to_string(),
+        )
+        .unwrap();
+
+        let actions = plan
+            .actions()
+            .iter()
+            .map(|planned| planned.action.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            [
+                "pull db (postgres:17)",
+                "~ recreate db (1 replicas)",
+                "~ scale api 1 -> 3",
+                "- stop cache",
+                "~ recreate web (1 replicas)",
+                "+ create queue (1 replicas)",
+                "- remove worker (app-worker-1)",
+            ]
+        );
+        assert_eq!(plan.actions()[1].reason, "configuration changed");
+        assert_eq!(
+            plan.actions()[4].reason,
+            "container app-web-1 runs an outdated image of nginx:1"
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+
+use crate::{
+    command::{CatchOutput, ExecOptions},
+    http, Compose, ComposeCommand, ComposeError, Endpoints,
+};
+
+/// The shortest time a probe attempt gets, even if the deadline has passed.
+const MIN_ATTEMPT: Duration = Duration::from_millis(100);
+
+/// A check that decides whether a service is ready.
+#[derive(Debug, Clone)]
+pub enum Probe {
+    /// Connect to the host port published for the given container port
+    Tcp(u16),
+    /// GET `path` on the host port published for `port` and expect `status`
+    Http {
+        port: u16,
+        path: String,
+        status: u16,
+    },
+    /// A line of the service logs matches the regex
+    LogLine(Regex),
+    /// Execute a command in the service container, ready once it exits with 0
+    Exec(Vec<String>),
+}
+
+impl Probe {
+    pub fn tcp(port: u16) -> Self {
+        Self::Tcp(port)
+    }
+
+    /// GET `path` and expect a `200` status.
+    pub fn http(port: u16, path: &str) -> Self {
+        Self::Http {
+            port,
+            path: path.to_string(),
+            status: 200,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns [ComposeError::InvalidArguments] if the pattern is not a valid regex.
+    pub fn log_line(pattern: &str) -> Result<Self, ComposeError> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| ComposeError::InvalidArguments(format!("Invalid log pattern: {e}")))?;
+
+        Ok(Self::LogLine(regex))
+    }
+
+    pub fn exec<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        Self::Exec(args.into_iter().map(|arg| arg.to_string()).collect())
+    }
+}
+
+/// A set of per-service [Probe]s with an overall timeout, see [Compose::wait_until].
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    probes: Vec<(String, Probe)>,
+    timeout: Duration,
+    interval: Duration,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            probes: Vec::new(),
+            timeout: Duration::from_secs(60),
+            interval: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a probe for a service, a service can have multiple probes.
+    pub fn probe(mut self, service: &str, probe: Probe) -> Self {
+        self.probes.push((service.to_string(), probe));
+        self
+    }
+
+    /// The time all probes have to succeed in, defaults to 60 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The delay between probe attempts, defaults to 500 milliseconds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Compose {
+    /// Block until all probes of `readiness` succeed.
+    ///
+    /// The commands a probe runs, like `ps` to find a published port or `exec`, are
+    /// killed once the timeout expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ComposeError::NotReady] with the first service that did not become ready
+    /// within the timeout, along with the reason of its last failed probe.
+    pub fn wait_until(&self, readiness: Readiness) -> Result<(), ComposeError> {
+        let deadline = Instant::now() + readiness.timeout;
+
+        wait(readiness, deadline, |service, probe, remaining| {
+            self.check(service, probe, remaining)
+        })
+    }
+
+    /// Run a probe once, bounded by the time that is left, and return why it failed.
+    fn check(&self, service: &str, probe: &Probe, remaining: Duration) -> Result<(), String> {
+        let options = ExecOptions {
+            timeout: Some(remaining),
+            ..self.exec_options()
+        };
+
+        match probe {
+            Probe::Tcp(port) | Probe::Http { port, .. } => {
+                let containers = self
+                    .ps()
+                    .with_options(options)
+                    .exec()
+                    .map_err(|e| e.to_string())?;
+                let addr = Endpoints::from_containers(&containers)
+                    .first(service, *port)
+                    .ok_or_else(|| format!("Port {port} is not published"))?;
+
+                probe_addr(addr, probe, remaining)
+            }
+            Probe::LogLine(regex) => {
+                let mut command = self.init_command();
+                command.arg("logs").arg("--no-color").arg(service);
+
+                let output = command
+                    .catch_output_with(&options)
+                    .map_err(|e| e.to_string())?;
+                let logs = String::from_utf8_lossy(&output.stdout);
+
+                match logs.lines().any(|line| regex.is_match(line)) {
+                    true => Ok(()),
+                    false => Err(format!("No log line matches {}", regex.as_str())),
+                }
+            }
+            Probe::Exec(args) => {
+                let mut command = self.init_command();
+                command.arg("exec").arg("-T").arg(service).args(args);
+
+                command
+                    .catch_output_with(&options)
+                    .map_err(|e| e.to_string())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Run the probes every interval until all succeeded or the deadline passed. `check`
+/// gets the time that is left and returns why a probe failed.
+fn wait<F>(readiness: Readiness, deadline: Instant, mut check: F) -> Result<(), ComposeError>
+where
+    F: FnMut(&str, &Probe, Duration) -> Result<(), String>,
+{
+    let mut pending: Vec<(String, Probe, String)> = readiness
+        .probes
+        .into_iter()
+        .map(|(service, probe)| (service, probe, "not probed".to_string()))
+        .collect();
+
+    loop {
+        for (service, probe, reason) in pending.iter_mut() {
+            // A probe that starts right at the deadline still gets a short attempt
+            let remaining = deadline
+                .saturating_duration_since(Instant::now())
+                .max(MIN_ATTEMPT);
+
+            match check(service, probe, remaining) {
+                Ok(()) => reason.clear(),
+                Err(err) => *reason = err,
+            }
+        }
+        pending.retain(|(_, _, reason)| !reason.is_empty());
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            let (service, _, reason) = pending.swap_remove(0);
+            return Err(ComposeError::NotReady(service, reason));
+        }
+
+        thread::sleep(readiness.interval.min(deadline - now));
+    }
+}
+
+/// Connect to the published address of a TCP or HTTP probe.
+fn probe_addr(addr: SocketAddr, probe: &Probe, timeout: Duration) -> Result<(), String> {
+    match probe {
+        Probe::Tcp(_) => {
+            TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+            Ok(())
+        }
+        Probe::Http { path, status, .. } => {
+            let actual = http::request(addr, &addr.to_string(), "GET", path, None, timeout)
+                .map_err(|e| e.to_string())?;
+
+            match actual == *status {
+                true => Ok(()),
+                false => Err(format!("Expected status {status}, got {actual}")),
+            }
+        }
+        _ => Err("Not a network probe".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_tcp_and_http_probes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let timeout = Duration::from_secs(1);
+
+        let server = thread::spawn(move || {
+            for status in ["200 OK", "503 Service Unavailable"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                while !request.ends_with(b"\r\n\r\n") {
+                    let mut buf = [0; 512];
+                    let read = stream.read(&mut buf).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                }
+                write!(stream, "HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n").unwrap();
+            }
+        });
+
+        assert_eq!(
+            probe_addr(addr, &Probe::http(80, "/health"), timeout),
+            Ok(())
+        );
+        assert_eq!(
+            probe_addr(addr, &Probe::http(80, "/health"), timeout),
+            Err("Expected status 200, got 503".to_string())
+        );
+        server.join().unwrap();
+
+        // The listener is dropped, so nothing accepts connections on the port anymore
+        assert_eq!(probe_addr(addr, &Probe::tcp(80), timeout).ok(), None);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_eq!(probe_addr(addr, &Probe::tcp(80), timeout), Ok(()));
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let ready = listener.local_addr().unwrap();
+
+        let readiness = Readiness::new()
+            .probe("api", Probe::tcp(80))
+            .probe("db", Probe::tcp(5432))
+            .timeout(Duration::from_millis(300))
+            .interval(Duration::from_millis(50));
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(300);
+        let mut attempts = Vec::new();
+        let result = wait(readiness, deadline, |service, probe, remaining| {
+            attempts.push(remaining);
+            match service {
+                "api" => probe_addr(ready, probe, remaining),
+                _ => probe_addr(addr, probe, remaining),
+            }
+        });
+
+        let Err(ComposeError::NotReady(service, reason)) = result else {
+            panic!("unexpected result {result:?}");
+        };
+        assert_eq!(service, "db");
+        assert!(!reason.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(attempts
+            .iter()
+            .all(|remaining| *remaining <= Duration::from_millis(300)));
+    }
+}
@@ -0,0 +1,333 @@
+//! Retrying commands that failed for transient reasons, like registry hiccups during a pull.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{CancellationToken, ComposeError};
+
+/// A failed attempt that is about to be retried, passed to [RetryPolicy::on_retry].
+#[derive(Debug)]
+pub struct Retry<'a> {
+    /// The attempt that failed, starting at 1
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// How long until the next attempt
+    pub delay: Duration,
+    pub error: &'a ComposeError,
+}
+
+type Predicate = Arc<dyn Fn(&ComposeError) -> bool + Send + Sync>;
+type RetryCallback = Arc<dyn Fn(&Retry) + Send + Sync>;
+
+/// How often and when a failed command is retried.
+///
+/// Commands are retried with an exponential backoff, by default up to 3 attempts
+/// when [ComposeError::is_transient] holds.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    predicate: Predicate,
+    on_retry: Option<RetryCallback>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            predicate: Arc::new(ComposeError::is_transient),
+            on_retry: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retry transient failures until `max_attempts` attempts have been made.
+    pub fn new(max_attempts: u32) -> Self {
+        Self::default().max_attempts(max_attempts)
+    }
+
+    /// Never retry, e.g. to override the policy of a [crate::Compose] for one command.
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    /// The total number of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// The delay before the first retry and the limit the delay grows to.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// The factor the delay grows by after each retry, defaults to 2.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// The fraction of the delay that is randomized, between 0 and 1, defaults to 0.2.
+    ///
+    /// Jitter keeps clients that failed together from retrying in lockstep.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Only retry errors the predicate holds for, replacing [ComposeError::is_transient].
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&ComposeError) -> bool + Send + Sync + 'static,
+    {
+        self.predicate = Arc::new(predicate);
+        self
+    }
+
+    /// Call a function before each retry, e.g. to log the attempt.
+    pub fn on_retry<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Retry) + Send + Sync + 'static,
+    {
+        self.on_retry = Some(Arc::new(callback));
+        self
+    }
+
+    /// The delay before the attempt after `attempt`, without jitter.
+    fn backoff_after(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        self.initial_backoff
+            .mul_f64(factor.min(u32::MAX as f64))
+            .min(self.max_backoff)
+    }
+
+    fn delay_after(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff_after(attempt);
+        // A random fraction in [0, 1) without pulling in a random number generator
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        backoff.mul_f64(1.0 - self.jitter * random)
+    }
+
+    /// Run `f` until it succeeds, the error is not retryable or the attempts are used up.
+    ///
+    /// Waiting for the next attempt ends early with [ComposeError::Cancelled] if the
+    /// token is cancelled, the error carries the failure of the last attempt.
+    pub(crate) fn run<T>(
+        &self,
+        cancellation: Option<&CancellationToken>,
+        mut f: impl FnMut() -> Result<T, ComposeError>,
+    ) -> Result<T, ComposeError> {
+        let mut attempt = 1;
+        loop {
+            let error = match f() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            if attempt >= self.max_attempts || !(self.predicate)(&error) {
+                return Err(error);
+            }
+
+            let delay = self.delay_after(attempt);
+            if let Some(on_retry) = &self.on_retry {
+                on_retry(&Retry {
+                    attempt,
+                    max_attempts: self.max_attempts,
+                    delay,
+                    error: &error,
+                });
+            }
+
+            let deadline = Instant::now() + delay;
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                if cancellation.is_some_and(CancellationToken::is_cancelled) {
+                    return Err(match error.failure() {
+                        Some(failure) => ComposeError::Cancelled(failure.clone()),
+                        None => error,
+                    });
+                }
+                thread::sleep(remaining.min(Duration::from_millis(10)));
+            }
+
+            attempt += 1;
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    };
+
+    use super::*;
+    use crate::CommandFailure;
+
+    fn transient() -> ComposeError {
+        ComposeError::CommandFailed(CommandFailure {
+            argv: vec![
+                "docker".to_string(),
+                "compose".to_string(),
+                "pull".to_string(),
+            ],
+            exit_code: Some(1),
+            stdout: String::new(),
+            stderr: "Get \"https://registry-1.docker.io/v2/\": net/http: TLS handshake timeout"
+                .to_string(),
+        })
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(10)
+            .backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .jitter(0.0);
+
+        assert_eq!(policy.backoff_after(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff_after(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff_after(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff_after(5), Duration::from_secs(1));
+
+        let policy = policy.jitter(0.5);
+        for _ in 0..20 {
+            let delay = policy.delay_after(2);
+            assert!(delay > Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn test_retry() {
+        let retries = Arc::new(Mutex::new(Vec::new()));
+        let recorded = retries.clone();
+        let policy = RetryPolicy::new(3)
+            .backoff(Duration::from_millis(1), Duration::from_millis(1))
+            .on_retry(move |retry| recorded.lock().unwrap().push(retry.attempt));
+
+        // Succeeds on the third attempt
+        let calls = AtomicU32::new(0);
+        let result = policy.run(None, || match calls.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => Err(transient()),
+            _ => Ok("pulled"),
+        });
+        assert_eq!(result.unwrap(), "pulled");
+        assert_eq!(*retries.lock().unwrap(), [1, 2]);
+
+        // Gives up after the last attempt
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = policy.run(None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(transient())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Errors that are not transient are returned right away
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = policy.run(None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(ComposeError::InvalidArguments("no services".to_string()))
+        });
+        assert!(matches!(result, Err(ComposeError::InvalidArguments(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Retrying a timeout would multiply the bound of the call
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = policy.run(None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let ComposeError::CommandFailed(failure) = transient() else {
+                unreachable!()
+            };
+            Err(ComposeError::Timeout {
+                timeout: Duration::from_secs(60),
+                failure,
+            })
+        });
+        assert!(matches!(result, Err(ComposeError::Timeout { .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A custom predicate replaces the default one
+        let policy = RetryPolicy::new(2)
+            .backoff(Duration::from_millis(1), Duration::from_millis(1))
+            .retry_if(|error| matches!(error, ComposeError::InvalidArguments(_)));
+        let calls = AtomicU32::new(0);
+        let _: Result<(), _> = policy.run(None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(ComposeError::InvalidArguments("no services".to_string()))
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_retry_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let policy = RetryPolicy::new(5).backoff(Duration::from_secs(5), Duration::from_secs(5));
+        let start = Instant::now();
+        let result: Result<(), _> = policy.run(Some(&token), || Err(transient()));
+
+        assert!(matches!(result, Err(ComposeError::Cancelled(_))));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_retry_stdin() {
+        use std::process::Command;
+
+        use crate::{command::CatchOutput, Compose};
+
+        let yaml = "services:\n  web:\n    image: nginx:alpine\n";
+        let compose = Compose::builder()
+            .yaml(yaml)
+            .project_directory(".")
+            .retry(RetryPolicy::new(2).backoff(Duration::from_millis(1), Duration::from_millis(1)))
+            .build()
+            .unwrap();
+
+        // Fails transiently on the first attempt, the retry has to read the file again
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("attempted");
+        let script = format!(
+            "file=$(cat); test -f {0} || {{ touch {0}; echo 'connection reset by peer' >&2; exit 1; }}; printf '%s\\n' \"$file\"",
+            marker.display()
+        );
+
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .catch_output_with(&compose.exec_options())
+            .unwrap();
+        assert_eq!(output.stdout, yaml.as_bytes());
+    }
+}
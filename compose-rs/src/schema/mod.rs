@@ -0,0 +1,433 @@
+//! Offline validation of compose files against the compose-spec JSON schema.
+//!
+//! Every violation is reported with the YAML path of the offending value and its
+//! position in the file, e.g. `docker-compose.yml:12:7: services.api.ports[0]: ...`.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use jsonschema::{error::ValidationErrorKind, Validator};
+use yaml_rust2::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+};
+
+use crate::{interpolation::interpolate, merge, Compose, ComposeError};
+
+/// The bundled compose-spec JSON schema.
+pub const COMPOSE_SPEC_SCHEMA: &str = include_str!("compose-spec.json");
+
+/// A value of a compose file that does not conform to the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    pub file: Option<PathBuf>,
+    /// YAML path of the value, e.g. `services.api.ports[0]`, empty for the document itself
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        write!(f, "{}:{}: ", self.line, self.column)?;
+
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Validate the text of a compose file against the bundled schema.
+///
+/// Merge tags like `!reset` and YAML merge keys are resolved before validation,
+/// variables are not interpolated.
+pub fn validate(text: &str, file: Option<&Path>) -> Result<(), Vec<SchemaViolation>> {
+    let instance = parse(text, file)?;
+    check(&instance, text, file)
+}
+
+fn validator() -> &'static Validator {
+    static VALIDATOR: OnceLock<Validator> = OnceLock::new();
+
+    VALIDATOR.get_or_init(|| {
+        let schema = serde_json::from_str(COMPOSE_SPEC_SCHEMA).expect("bundled schema is JSON");
+        jsonschema::validator_for(&schema).expect("bundled schema is valid")
+    })
+}
+
+/// Parse a compose file into the JSON instance the schema is checked against.
+fn parse(text: &str, file: Option<&Path>) -> Result<serde_json::Value, Vec<SchemaViolation>> {
+    let violation = |error: serde_yaml::Error| {
+        let location = error.location();
+        vec![SchemaViolation {
+            file: file.map(Path::to_path_buf),
+            path: String::new(),
+            line: location.as_ref().map_or(1, |l| l.line()),
+            column: location.as_ref().map_or(1, |l| l.column()),
+            message: error.to_string(),
+        }]
+    };
+
+    let mut value = serde_yaml::from_str::<serde_yaml::Value>(text).map_err(violation)?;
+    value.apply_merge().map_err(violation)?;
+
+    Ok(to_json(merge::merge_all([value])))
+}
+
+/// Check an instance, `text` is the source the positions are looked up in.
+fn check(
+    instance: &serde_json::Value,
+    text: &str,
+    file: Option<&Path>,
+) -> Result<(), Vec<SchemaViolation>> {
+    let positions = Positions::index(text);
+
+    let violations = validator()
+        .iter_errors(instance)
+        .map(|error| {
+            let mut segments = error
+                .instance_path
+                .as_str()
+                .split('/')
+                .skip(1)
+                .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+                .collect::<Vec<_>>();
+
+            // Point at the first unexpected key rather than the mapping containing it
+            if let ValidationErrorKind::AdditionalProperties { unexpected } = &error.kind {
+                if let [key, ..] = unexpected.as_slice() {
+                    segments.push(key.clone());
+                }
+            }
+
+            let (line, column) = positions.find(&segments);
+            SchemaViolation {
+                file: file.map(Path::to_path_buf),
+                path: yaml_path(instance, &segments),
+                line,
+                column,
+                message: error.to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    match violations.is_empty() {
+        true => Ok(()),
+        false => Err(violations),
+    }
+}
+
+/// Format path segments as a YAML path, sequence indices are written as `[n]`.
+fn yaml_path(instance: &serde_json::Value, segments: &[String]) -> String {
+    let mut path = String::new();
+    let mut value = Some(instance);
+
+    for segment in segments {
+        match value {
+            Some(serde_json::Value::Array(items)) => {
+                path.push_str(&format!("[{segment}]"));
+                value = segment.parse::<usize>().ok().and_then(|i| items.get(i));
+            }
+            _ => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(segment);
+                value = value.and_then(|value| value.get(segment));
+            }
+        }
+    }
+
+    path
+}
+
+fn to_json(value: serde_yaml::Value) -> serde_json::Value {
+    use serde_yaml::Value;
+
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Bool(b) => serde_json::Value::Bool(b),
+        Value::Number(n) => serde_json::to_value(n).unwrap_or(serde_json::Value::Null),
+        Value::String(s) => serde_json::Value::String(s),
+        Value::Sequence(items) => {
+            serde_json::Value::Array(items.into_iter().map(to_json).collect())
+        }
+        Value::Mapping(mapping) => serde_json::Value::Object(
+            mapping
+                .into_iter()
+                .map(|(key, value)| (key_string(key), to_json(value)))
+                .collect(),
+        ),
+        Value::Tagged(tagged) => to_json(tagged.value),
+    }
+}
+
+fn key_string(key: serde_yaml::Value) -> String {
+    match key {
+        serde_yaml::Value::String(s) => s,
+        key => serde_yaml::to_string(&key)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Line and column of every key and sequence item of a YAML document, by path.
+pub(crate) struct Positions {
+    positions: HashMap<Vec<String>, (usize, usize)>,
+    /// Where the content of scalar values starts, after the opening quote, and their style
+    scalars: HashMap<Vec<String>, (usize, usize, TScalarStyle)>,
+}
+
+impl Positions {
+    pub(crate) fn index(text: &str) -> Self {
+        let mut indexer = Indexer::default();
+        // Syntax errors are reported by the parse, positions are best effort
+        let _ = Parser::new_from_str(text).load(&mut indexer, false);
+
+        Self {
+            positions: indexer.positions,
+            scalars: indexer.scalars,
+        }
+    }
+
+    /// The position the content of the scalar value at the path starts at, or the
+    /// position of its closest known ancestor.
+    pub(crate) fn scalar(&self, segments: &[String]) -> (usize, usize) {
+        match self.scalars.get(segments) {
+            Some((line, column, _)) => (*line, *column),
+            None => self.find(segments),
+        }
+    }
+
+    /// Whether the scalar value at the path is written without quotes or block indicators.
+    pub(crate) fn is_plain(&self, segments: &[String]) -> bool {
+        matches!(self.scalars.get(segments), Some((.., TScalarStyle::Plain)))
+    }
+
+    /// The position of the path, or of its closest ancestor that is known.
+    fn find(&self, segments: &[String]) -> (usize, usize) {
+        self.closest(segments)
+            .map_or((1, 1), |(_, position)| position)
+    }
+
+    /// The number of segments of the closest known ancestor of the path, and its position.
+    pub(crate) fn closest(&self, segments: &[String]) -> Option<(usize, (usize, usize))> {
+        (0..=segments.len())
+            .rev()
+            .find_map(|len| Some((len, *self.positions.get(&segments[..len])?)))
+    }
+}
+
+enum Frame {
+    Mapping { key: Option<String> },
+    Sequence { index: usize },
+}
+
+#[derive(Default)]
+struct Indexer {
+    stack: Vec<Frame>,
+    positions: HashMap<Vec<String>, (usize, usize)>,
+    scalars: HashMap<Vec<String>, (usize, usize, TScalarStyle)>,
+}
+
+impl Indexer {
+    fn path(&self) -> Vec<String> {
+        self.stack
+            .iter()
+            .map(|frame| match frame {
+                Frame::Mapping { key } => key.clone().unwrap_or_default(),
+                Frame::Sequence { index } => index.to_string(),
+            })
+            .collect()
+    }
+
+    fn record(&mut self, mark: Marker) {
+        self.positions
+            .insert(self.path(), (mark.line(), mark.col() + 1));
+    }
+
+    /// Called before a value is read, records the position of sequence items.
+    fn value_start(&mut self, mark: Marker) {
+        if let Some(Frame::Sequence { .. }) = self.stack.last() {
+            self.record(mark);
+        }
+    }
+
+    /// Called after a value is read, advances the enclosing collection.
+    fn value_end(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping { key }) => *key = None,
+            Some(Frame::Sequence { index }) => *index += 1,
+            None => {}
+        }
+    }
+
+    /// Whether the next node is the key of a mapping entry.
+    fn expects_key(&self) -> bool {
+        matches!(self.stack.last(), Some(Frame::Mapping { key: None }))
+    }
+}
+
+impl MarkedEventReceiver for Indexer {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) if self.expects_key() => {
+                if let Some(Frame::Mapping { key }) = self.stack.last_mut() {
+                    *key = Some(value);
+                }
+                self.record(mark);
+            }
+            Event::Scalar(_, style, ..) => {
+                let quote = matches!(
+                    style,
+                    TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted
+                );
+                self.scalars.insert(
+                    self.path(),
+                    (mark.line(), mark.col() + 1 + quote as usize, style),
+                );
+                self.value_start(mark);
+                self.value_end();
+            }
+            Event::Alias(..) => {
+                self.value_start(mark);
+                self.value_end();
+            }
+            Event::MappingStart(..) => {
+                self.value_start(mark);
+                self.stack.push(Frame::Mapping { key: None });
+            }
+            Event::SequenceStart(..) => {
+                self.value_start(mark);
+                self.stack.push(Frame::Sequence { index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.value_end();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Compose {
+    /// Validate the compose files against the bundled compose-spec schema, without
+    /// invoking docker.
+    ///
+    /// Variables are interpolated before validation, like compose does, while positions
+    /// refer to the files as written.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ComposeError::SchemaError] with every violation of all files, or
+    /// [ComposeError::InterpolationError] if a variable can't be interpolated.
+    pub fn validate(&self) -> Result<(), ComposeError> {
+        let environment = self.environment()?;
+
+        let mut violations = Vec::new();
+        let mut errors = Vec::new();
+        for (path, content) in self.sources()? {
+            let interpolated = match interpolate(&content, path, &environment) {
+                Ok(interpolated) => interpolated,
+                Err(e) => {
+                    errors.extend(e);
+                    continue;
+                }
+            };
+
+            let result =
+                parse(&interpolated, path).and_then(|instance| check(&instance, &content, path));
+            if let Err(e) = result {
+                violations.extend(e);
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ComposeError::InterpolationError(errors));
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(ComposeError::SchemaError(violations)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_valid_file() {
+        let text = r#"
+x-defaults: &defaults
+  restart: always
+services:
+  api:
+    <<: *defaults
+    build: ./api
+    ports: ["8080:8080", 9000]
+    environment:
+      - RUST_LOG=info
+    depends_on:
+      db:
+        condition: service_healthy
+    labels: !reset {}
+  db:
+    image: postgres:16
+    healthcheck:
+      test: ["CMD", "pg_isready"]
+      interval: 5s
+volumes:
+  data:
+"#;
+
+        assert_eq!(validate(text, None), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_all_violations() {
+        let text = "services:
+  api:
+    image: api
+    prots:
+      - 8080:8080
+    depends_on:
+      db:
+        condition: started
+  db:
+    image: [postgres]
+";
+
+        let violations = validate(text, Some(Path::new("compose.yml"))).unwrap_err();
+        let find = |path: &str| {
+            violations
+                .iter()
+                .find(|v| v.path == path)
+                .unwrap_or_else(|| panic!("no violation at {path}: {violations:?}"))
+        };
+
+        assert_eq!(violations.len(), 3);
+        assert_eq!(
+            (
+                find("services.api.prots").line,
+                find("services.api.prots").column
+            ),
+            (4, 5)
+        );
+        assert_eq!(find("services.api.depends_on").line, 6);
+        assert_eq!(find("services.db.image").line, 10);
+        assert!(find("services.db.image")
+            .to_string()
+            .starts_with("compose.yml:10:5: services.db.image: "));
+    }
+}